@@ -15,13 +15,30 @@ pub struct DateTime(pub String);
 #[cynic(graphql_type = "URI")]
 pub struct Uri(pub String);
 
-#[derive(cynic::QueryFragment, Debug)]
+#[derive(cynic::QueryFragment, Debug, Clone)]
 pub struct Discussion {
     pub id: cynic::Id,
+    pub number: i32,
     pub title: String,
     pub created_at: DateTime,
     pub url: Uri,
+    pub body: String,
     pub body_text: String,
+    pub locked: bool,
+    pub closed: bool,
+    pub category: DiscussionCategory,
+}
+
+#[derive(cynic::QueryFragment, Debug, Clone)]
+pub struct RateLimit {
+    pub cost: i32,
+    pub remaining: i32,
+    pub reset_at: DateTime,
+}
+
+/// Implemented by queries that request the `rateLimit` field alongside their results.
+pub trait RateLimited {
+    fn rate_limit(&self) -> Option<&RateLimit>;
 }
 
 #[derive(cynic::QueryFragment, Debug)]
@@ -43,6 +60,13 @@ pub struct RepoIdQueryVariables<'a> {
 pub struct RepoIdQuery {
     #[arguments(owner: $owner, name: $repo_name)]
     pub repository: Option<RepoIdQueryRepository>,
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl RateLimited for RepoIdQuery {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
 }
 
 #[derive(cynic::QueryFragment, Debug)]
@@ -65,6 +89,13 @@ pub struct CategoryQueryVariables<'a> {
 pub struct CategoryQuery {
     #[arguments(owner: $owner, name: $repo_name)]
     pub repository: Option<CategoryQueryRepository>,
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl RateLimited for CategoryQuery {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
 }
 
 #[derive(cynic::QueryFragment, Debug)]
@@ -87,10 +118,67 @@ pub struct DiscussionCategoryEdge {
     pub cursor: String,
 }
 
-#[derive(cynic::QueryFragment, Debug)]
+#[derive(cynic::QueryFragment, Debug, Clone)]
 pub struct DiscussionCategory {
     pub id: cynic::Id,
     pub name: String,
+    pub is_answerable: bool,
+}
+
+// query PreflightQuery
+
+/// Ordered from least to most privileged.
+#[derive(cynic::Enum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RepositoryPermission {
+    Read,
+    Triage,
+    Write,
+    Maintain,
+    Admin,
+}
+
+#[derive(cynic::QueryVariables, Debug, Clone)]
+pub struct PreflightQueryVariables<'a> {
+    pub owner: &'a str,
+    pub repo_name: &'a str,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Query", variables = "PreflightQueryVariables")]
+pub struct PreflightQuery {
+    #[arguments(owner: $owner, name: $repo_name)]
+    pub repository: Option<PreflightQueryRepository>,
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl RateLimited for PreflightQuery {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Repository")]
+pub struct PreflightQueryRepository {
+    pub has_discussions_enabled: bool,
+    pub viewer_permission: Option<RepositoryPermission>,
+    #[arguments(first: 100)]
+    pub discussion_categories: PreflightCategoryConnection,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "DiscussionCategoryConnection")]
+pub struct PreflightCategoryConnection {
+    #[cynic(flatten)]
+    pub nodes: Vec<PreflightCategory>,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "DiscussionCategory")]
+pub struct PreflightCategory {
+    pub name: String,
+    /// URL-friendly identifier, `announcements` for the category GitHub creates by default.
+    pub slug: String,
 }
 
 // query DiscussionExists
@@ -99,7 +187,8 @@ pub struct DiscussionCategory {
 pub struct DiscussionExistsVariables<'a> {
     pub owner: &'a str,
     pub repo_name: &'a str,
-    pub cat_id: cynic::Id,
+    /// Searches every category when `None`.
+    pub cat_id: Option<cynic::Id>,
     pub after_cursor: Option<String>,
 }
 
@@ -108,6 +197,13 @@ pub struct DiscussionExistsVariables<'a> {
 pub struct DiscussionExists {
     #[arguments(owner: $owner, name: $repo_name)]
     pub repository: Option<DiscussionExistsRepository>,
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl RateLimited for DiscussionExists {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
 }
 
 #[derive(cynic::QueryFragment, Debug)]
@@ -150,11 +246,326 @@ pub struct CreateCommentsDiscussion {
     pub create_discussion: Option<CreateDiscussionPayload>,
 }
 
+// Mutations can't request `rateLimit`, so their cost isn't tracked.
+impl RateLimited for CreateCommentsDiscussion {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        None
+    }
+}
+
 #[derive(cynic::QueryFragment, Debug)]
 pub struct CreateDiscussionPayload {
     pub discussion: Option<Discussion>,
 }
 
+// query LabelsQuery
+
+#[derive(cynic::QueryVariables, Debug, Clone)]
+pub struct LabelsQueryVariables<'a> {
+    pub owner: &'a str,
+    pub repo_name: &'a str,
+    pub after_cursor: Option<String>,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Query", variables = "LabelsQueryVariables")]
+pub struct LabelsQuery {
+    #[arguments(owner: $owner, name: $repo_name)]
+    pub repository: Option<LabelsQueryRepository>,
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl RateLimited for LabelsQuery {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Repository", variables = "LabelsQueryVariables")]
+pub struct LabelsQueryRepository {
+    #[arguments(first: 100, after: $after_cursor)]
+    pub labels: Option<LabelConnection>,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+pub struct LabelConnection {
+    #[cynic(flatten)]
+    pub edges: Vec<LabelEdge>,
+    pub page_info: PageInfo,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+pub struct LabelEdge {
+    pub node: Option<Label>,
+}
+
+#[derive(cynic::QueryFragment, Debug, Clone)]
+pub struct Label {
+    pub id: cynic::Id,
+    pub name: String,
+}
+
+// mutation AddLabelsToDiscussion
+
+#[derive(cynic::QueryVariables, Debug)]
+pub struct AddLabelsToDiscussionVariables {
+    pub discussion_id: cynic::Id,
+    pub label_ids: Vec<cynic::Id>,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(
+    graphql_type = "Mutation",
+    variables = "AddLabelsToDiscussionVariables"
+)]
+pub struct AddLabelsToDiscussion {
+    #[arguments(input: { clientMutationId: "rss_autogen_giscus", labelableId: $discussion_id, labelIds: $label_ids })]
+    pub add_labels_to_labelable: Option<AddLabelsToLabelablePayload>,
+}
+
+impl RateLimited for AddLabelsToDiscussion {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        None
+    }
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+pub struct AddLabelsToLabelablePayload {
+    pub client_mutation_id: Option<String>,
+}
+
+// mutation LockDiscussion
+
+#[derive(cynic::QueryVariables, Debug)]
+pub struct LockDiscussionVariables {
+    pub discussion_id: cynic::Id,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Mutation", variables = "LockDiscussionVariables")]
+pub struct LockDiscussion {
+    #[arguments(input: { clientMutationId: "rss_autogen_giscus", lockableId: $discussion_id })]
+    pub lock_lockable: Option<LockLockablePayload>,
+}
+
+impl RateLimited for LockDiscussion {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        None
+    }
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+pub struct LockLockablePayload {
+    pub client_mutation_id: Option<String>,
+}
+
+// query DiscussionComments
+
+#[derive(cynic::QueryVariables, Debug, Clone)]
+pub struct DiscussionCommentsVariables<'a> {
+    pub owner: &'a str,
+    pub repo_name: &'a str,
+    pub number: i32,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Query", variables = "DiscussionCommentsVariables")]
+pub struct DiscussionComments {
+    #[arguments(owner: $owner, name: $repo_name)]
+    pub repository: Option<DiscussionCommentsRepository>,
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl RateLimited for DiscussionComments {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Repository", variables = "DiscussionCommentsVariables")]
+pub struct DiscussionCommentsRepository {
+    #[arguments(number: $number)]
+    pub discussion: Option<DiscussionCommentsDiscussion>,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Discussion")]
+pub struct DiscussionCommentsDiscussion {
+    #[arguments(last: 100)]
+    pub comments: DiscussionCommentConnection,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+pub struct DiscussionCommentConnection {
+    #[cynic(flatten)]
+    pub nodes: Vec<DiscussionCommentBody>,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "DiscussionComment")]
+pub struct DiscussionCommentBody {
+    pub body: String,
+}
+
+// mutation AddComment
+
+#[derive(cynic::QueryVariables, Debug)]
+pub struct AddCommentVariables {
+    pub discussion_id: cynic::Id,
+    pub body: String,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Mutation", variables = "AddCommentVariables")]
+pub struct AddComment {
+    #[arguments(input: { clientMutationId: "rss_autogen_giscus", discussionId: $discussion_id, body: $body })]
+    pub add_discussion_comment: Option<AddDiscussionCommentPayload>,
+}
+
+impl RateLimited for AddComment {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        None
+    }
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+pub struct AddDiscussionCommentPayload {
+    pub comment: Option<DiscussionComment>,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+pub struct DiscussionComment {
+    pub id: cynic::Id,
+    pub url: Uri,
+}
+
+// mutation MarkCommentAsAnswer
+
+#[derive(cynic::QueryVariables, Debug)]
+pub struct MarkCommentAsAnswerVariables {
+    pub comment_id: cynic::Id,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Mutation", variables = "MarkCommentAsAnswerVariables")]
+pub struct MarkCommentAsAnswer {
+    #[arguments(input: { clientMutationId: "rss_autogen_giscus", id: $comment_id })]
+    pub mark_discussion_comment_as_answer: Option<MarkDiscussionCommentAsAnswerPayload>,
+}
+
+impl RateLimited for MarkCommentAsAnswer {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        None
+    }
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+pub struct MarkDiscussionCommentAsAnswerPayload {
+    pub client_mutation_id: Option<String>,
+}
+
+// mutation CloseDiscussion
+
+#[derive(cynic::QueryVariables, Debug)]
+pub struct CloseDiscussionVariables {
+    pub discussion_id: cynic::Id,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Mutation", variables = "CloseDiscussionVariables")]
+pub struct CloseDiscussion {
+    #[arguments(input: { clientMutationId: "rss_autogen_giscus", discussionId: $discussion_id, reason: "OUTDATED" })]
+    pub close_discussion: Option<CloseDiscussionPayload>,
+}
+
+impl RateLimited for CloseDiscussion {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        None
+    }
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+pub struct CloseDiscussionPayload {
+    pub client_mutation_id: Option<String>,
+}
+
+// mutation DeleteDiscussion
+
+#[derive(cynic::QueryVariables, Debug)]
+pub struct DeleteDiscussionVariables {
+    pub discussion_id: cynic::Id,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Mutation", variables = "DeleteDiscussionVariables")]
+pub struct DeleteDiscussion {
+    #[arguments(input: { clientMutationId: "rss_autogen_giscus", id: $discussion_id })]
+    pub delete_discussion: Option<DeleteDiscussionPayload>,
+}
+
+impl RateLimited for DeleteDiscussion {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        None
+    }
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+pub struct DeleteDiscussionPayload {
+    pub client_mutation_id: Option<String>,
+}
+
+// mutation UpdateDiscussionBody
+
+#[derive(cynic::QueryVariables, Debug)]
+pub struct UpdateDiscussionBodyVariables {
+    pub discussion_id: cynic::Id,
+    pub body: String,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(graphql_type = "Mutation", variables = "UpdateDiscussionBodyVariables")]
+pub struct UpdateDiscussionBody {
+    #[arguments(input: { clientMutationId: "rss_autogen_giscus", discussionId: $discussion_id, body: $body })]
+    pub update_discussion: Option<UpdateDiscussionPayload>,
+}
+
+impl RateLimited for UpdateDiscussionBody {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        None
+    }
+}
+
+// mutation UpdateDiscussionTitle
+
+#[derive(cynic::QueryVariables, Debug)]
+pub struct UpdateDiscussionTitleVariables {
+    pub discussion_id: cynic::Id,
+    pub title: String,
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(
+    graphql_type = "Mutation",
+    variables = "UpdateDiscussionTitleVariables"
+)]
+pub struct UpdateDiscussionTitle {
+    #[arguments(input: { clientMutationId: "rss_autogen_giscus", discussionId: $discussion_id, title: $title })]
+    pub update_discussion: Option<UpdateDiscussionPayload>,
+}
+
+impl RateLimited for UpdateDiscussionTitle {
+    fn rate_limit(&self) -> Option<&RateLimit> {
+        None
+    }
+}
+
+#[derive(cynic::QueryFragment, Debug)]
+pub struct UpdateDiscussionPayload {
+    pub discussion: Option<Discussion>,
+}
+
 impl From<Uri> for String {
     fn from(value: Uri) -> Self {
         value.0
@@ -198,6 +609,18 @@ mod tests {
         print!("{}", category_query_op.query);
     }
 
+    #[test]
+    fn preflight_query_output() {
+        use super::{PreflightQuery, PreflightQueryVariables};
+        use cynic::QueryBuilder;
+
+        let preflight_query_op = PreflightQuery::build(PreflightQueryVariables {
+            owner: REPO_OWNER,
+            repo_name: REPO_NAME,
+        });
+        print!("{}", preflight_query_op.query);
+    }
+
     #[test]
     fn discussion_exists_output() {
         use super::{DiscussionExists, DiscussionExistsVariables};
@@ -206,7 +629,7 @@ mod tests {
         let discussion_exists_op = DiscussionExists::build(DiscussionExistsVariables {
             owner: REPO_OWNER,
             repo_name: REPO_NAME,
-            cat_id: Id::new("155"),
+            cat_id: Some(Id::new("155")),
             after_cursor: None,
         });
         print!("{}", discussion_exists_op.query);
@@ -227,4 +650,124 @@ mod tests {
 
         print!("{}", create_comments_discussion_op.query);
     }
+
+    #[test]
+    fn labels_query_output() {
+        use super::{LabelsQuery, LabelsQueryVariables};
+        use cynic::QueryBuilder;
+
+        let labels_query_op = LabelsQuery::build(LabelsQueryVariables {
+            owner: REPO_OWNER,
+            repo_name: REPO_NAME,
+            after_cursor: None,
+        });
+        print!("{}", labels_query_op.query);
+    }
+
+    #[test]
+    fn add_labels_to_discussion_output() {
+        use super::{AddLabelsToDiscussion, AddLabelsToDiscussionVariables};
+        use cynic::MutationBuilder;
+
+        let add_labels_op = AddLabelsToDiscussion::build(AddLabelsToDiscussionVariables {
+            discussion_id: "D_kwDOJSVgjc4ATest".into(),
+            label_ids: vec!["LA_kwDOJSVgjc8AAAABTest".into()],
+        });
+        print!("{}", add_labels_op.query);
+    }
+
+    #[test]
+    fn lock_discussion_output() {
+        use super::{LockDiscussion, LockDiscussionVariables};
+        use cynic::MutationBuilder;
+
+        let lock_discussion_op = LockDiscussion::build(LockDiscussionVariables {
+            discussion_id: "D_kwDOJSVgjc4ATest".into(),
+        });
+        print!("{}", lock_discussion_op.query);
+    }
+
+    #[test]
+    fn discussion_comments_output() {
+        use super::{DiscussionComments, DiscussionCommentsVariables};
+        use cynic::QueryBuilder;
+
+        let discussion_comments_op = DiscussionComments::build(DiscussionCommentsVariables {
+            owner: REPO_OWNER,
+            repo_name: REPO_NAME,
+            number: 1,
+        });
+        print!("{}", discussion_comments_op.query);
+    }
+
+    #[test]
+    fn add_comment_output() {
+        use super::{AddComment, AddCommentVariables};
+        use cynic::MutationBuilder;
+
+        let add_comment_op = AddComment::build(AddCommentVariables {
+            discussion_id: "D_kwDOJSVgjc4ATest".into(),
+            body: "Comments are now closed on this post.".to_string(),
+        });
+        print!("{}", add_comment_op.query);
+    }
+
+    #[test]
+    fn mark_comment_as_answer_output() {
+        use super::{MarkCommentAsAnswer, MarkCommentAsAnswerVariables};
+        use cynic::MutationBuilder;
+
+        let mark_comment_as_answer_op = MarkCommentAsAnswer::build(MarkCommentAsAnswerVariables {
+            comment_id: "DC_kwDOJSVgjc4AVTest".into(),
+        });
+        print!("{}", mark_comment_as_answer_op.query);
+    }
+
+    #[test]
+    fn close_discussion_output() {
+        use super::{CloseDiscussion, CloseDiscussionVariables};
+        use cynic::MutationBuilder;
+
+        let close_discussion_op = CloseDiscussion::build(CloseDiscussionVariables {
+            discussion_id: "D_kwDOJSVgjc4ATest".into(),
+        });
+        print!("{}", close_discussion_op.query);
+    }
+
+    #[test]
+    fn delete_discussion_output() {
+        use super::{DeleteDiscussion, DeleteDiscussionVariables};
+        use cynic::MutationBuilder;
+
+        let delete_discussion_op = DeleteDiscussion::build(DeleteDiscussionVariables {
+            discussion_id: "D_kwDOJSVgjc4ATest".into(),
+        });
+        print!("{}", delete_discussion_op.query);
+    }
+
+    #[test]
+    fn update_discussion_body_output() {
+        use super::{UpdateDiscussionBody, UpdateDiscussionBodyVariables};
+        use cynic::MutationBuilder;
+
+        let update_discussion_body_op =
+            UpdateDiscussionBody::build(UpdateDiscussionBodyVariables {
+                discussion_id: "D_kwDOJSVgjc4ATest".into(),
+                body: "Here is the new description of the post".to_string(),
+            });
+        print!("{}", update_discussion_body_op.query);
+    }
+
+    #[test]
+    fn update_discussion_title_output() {
+        use super::{UpdateDiscussionTitle, UpdateDiscussionTitleVariables};
+        use cynic::MutationBuilder;
+
+        let update_discussion_title_op =
+            UpdateDiscussionTitle::build(UpdateDiscussionTitleVariables {
+                discussion_id: "D_kwDOJSVgjc4ATest".into(),
+                title: "/blog/2023/most-recent-post/".to_string(),
+            });
+        print!("{}", update_discussion_title_op.query);
+    }
 }
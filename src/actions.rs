@@ -0,0 +1,257 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{Action, RunReport};
+
+/// Publishes the [run report](RunReport) to a GitHub Actions workflow run, so that later steps can
+/// use the result.
+///
+/// - The `discussion-url`, `discussion-number` and `created` step outputs are written to
+///   `$GITHUB_OUTPUT`, for the first post with a discussion.
+/// - A Markdown table of the processed posts is appended to `$GITHUB_STEP_SUMMARY`.
+/// - Warnings and errors are emitted as
+///   [workflow command](https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions)
+///   annotations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitHubActions {
+    /// Path of the step outputs file, from `GITHUB_OUTPUT`.
+    pub output_path: Option<PathBuf>,
+
+    /// Path of the job summary file, from `GITHUB_STEP_SUMMARY`.
+    pub summary_path: Option<PathBuf>,
+}
+
+impl GitHubActions {
+    /// Detects a workflow run from the `GITHUB_ACTIONS` environment variable.
+    pub fn from_env() -> Option<Self> {
+        if env::var("GITHUB_ACTIONS").as_deref() != Ok("true") {
+            return None;
+        }
+
+        Some(Self {
+            output_path: env::var_os("GITHUB_OUTPUT").map(PathBuf::from),
+            summary_path: env::var_os("GITHUB_STEP_SUMMARY").map(PathBuf::from),
+        })
+    }
+
+    /// Writes the step outputs, job summary and annotations for the report. Annotations are
+    /// written to `commands`, which the runner reads from either stdout or stderr.
+    pub fn publish(&self, report: &RunReport, mut commands: impl Write) -> io::Result<()> {
+        if let Some(path) = &self.output_path {
+            append(path, &step_outputs(report))?;
+        }
+        if let Some(path) = &self.summary_path {
+            append(path, &step_summary(report))?;
+        }
+        for annotation in annotations(report) {
+            writeln!(commands, "{annotation}")?;
+        }
+        Ok(())
+    }
+}
+
+fn append(path: &Path, contents: &str) -> io::Result<()> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(contents.as_bytes())
+}
+
+/// Formats the step outputs, in `name=value` lines.
+fn step_outputs(report: &RunReport) -> String {
+    let mut outputs = String::new();
+    if let Some(post) = report.posts.iter().find(|p| p.discussion_url.is_some()) {
+        outputs.push_str(&format!(
+            "discussion-url={}\n",
+            post.discussion_url.as_deref().unwrap_or_default()
+        ));
+        if let Some(number) = post.discussion_number {
+            outputs.push_str(&format!("discussion-number={number}\n"));
+        }
+    }
+    outputs.push_str(&format!(
+        "created={}\n",
+        report.posts.iter().any(|p| p.action == Action::Created)
+    ));
+    outputs
+}
+
+/// Formats the job summary, as a Markdown table of posts.
+fn step_summary(report: &RunReport) -> String {
+    let mut summary = String::from("### Giscus discussions\n\n");
+    if report.posts.is_empty() {
+        summary.push_str("No posts were processed.\n");
+    } else {
+        summary.push_str("| Post | Action | Discussion |\n| --- | --- | --- |\n");
+        for post in &report.posts {
+            let discussion = match (&post.discussion_url, post.discussion_number) {
+                (Some(url), Some(number)) => format!("[#{number}]({url})"),
+                (Some(url), None) => url.clone(),
+                _ => post.errors.join("<br>").replace('|', "\\|"),
+            };
+            summary.push_str(&format!(
+                "| [{}]({}) | {:?} | {} |\n",
+                post.mapping_term, post.url, post.action, discussion
+            ));
+        }
+    }
+
+    for error in &report.errors {
+        summary.push_str(&format!("\n> **Error:** {}\n", error.replace('\n', "\n> ")));
+    }
+    summary.push('\n');
+    summary
+}
+
+/// Formats the `::warning::` and `::error::` workflow commands for the report.
+fn annotations(report: &RunReport) -> Vec<String> {
+    let mut annotations = Vec::new();
+    for post in &report.posts {
+        for warning in &post.warnings {
+            annotations.push(command("warning", &post.url, warning));
+        }
+        match post.action {
+            Action::Skipped => annotations.push(command("warning", &post.url, "Post was skipped")),
+            Action::Failed => {
+                for error in &post.errors {
+                    annotations.push(command("error", &post.url, error));
+                }
+            }
+            Action::Orphaned => annotations.push(command(
+                "warning",
+                &post.url,
+                "Post was removed, but its discussion remains",
+            )),
+            Action::Created
+            | Action::Exists
+            | Action::Renamed
+            | Action::Updated
+            | Action::Locked
+            | Action::Closed
+            | Action::Deleted => {}
+        }
+    }
+    for error in &report.errors {
+        annotations.push(command("error", "rss_autogen_giscus", error));
+    }
+    annotations
+}
+
+/// Formats a workflow command, escaping the title and message.
+fn command(level: &str, title: &str, message: &str) -> String {
+    let escape_data = |s: &str| {
+        s.replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    let escape_property = |s: &str| escape_data(s).replace(':', "%3A").replace(',', "%2C");
+
+    format!(
+        "::{level} title={}::{}",
+        escape_property(title),
+        escape_data(message)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use url::Url;
+
+    use crate::actions::{annotations, command, step_outputs, step_summary};
+    use crate::{Action, GitHubActions, Post, PostReport, RunReport};
+
+    fn report() -> RunReport {
+        let post = |path: &str| Post {
+            id: None,
+            description: None,
+            url: Url::parse(&format!("https://example.com{path}")).unwrap(),
+            tags: Vec::new(),
+            meta: HashMap::new(),
+            giscus: None,
+        };
+
+        let mut created = PostReport::new(&post("/posts/new/"), Action::Created);
+        created.discussion_url = Some("https://github.com/example/comments/discussions/7".into());
+        created.discussion_number = Some(7);
+        let mut failed = PostReport::new(&post("/posts/broken/"), Action::Failed);
+        failed
+            .errors
+            .push("Invalid authentication token: Bad credentials".to_string());
+
+        RunReport {
+            posts: vec![created, failed],
+            ..RunReport::default()
+        }
+    }
+
+    #[test]
+    fn test_step_outputs() {
+        assert_eq!(
+            step_outputs(&report()),
+            "discussion-url=https://github.com/example/comments/discussions/7\ndiscussion-number=7\ncreated=true\n"
+        );
+        assert_eq!(step_outputs(&RunReport::default()), "created=false\n");
+    }
+
+    #[test]
+    fn test_step_summary() {
+        let summary = step_summary(&report());
+        assert!(summary.contains(
+            "| [/posts/new/](https://example.com/posts/new/) | Created | [#7](https://github.com/example/comments/discussions/7) |"
+        ));
+        assert!(summary.contains("| Failed | Invalid authentication token: Bad credentials |"));
+    }
+
+    #[test]
+    fn test_annotations() {
+        let annotations = annotations(&report());
+        assert_eq!(
+            annotations,
+            [
+                "::warning title=https%3A//example.com/posts/new/::No description was found in the post",
+                "::warning title=https%3A//example.com/posts/broken/::No description was found in the post",
+                "::error title=https%3A//example.com/posts/broken/::Invalid authentication token: Bad credentials",
+            ]
+        );
+        assert_eq!(
+            command("error", "run", "Preflight checks failed:\n- 100%"),
+            "::error title=run::Preflight checks failed:%0A- 100%25"
+        );
+    }
+
+    #[test]
+    fn test_publish() {
+        let output_path =
+            env::temp_dir().join(format!("rss_autogen_giscus_test_output_{}", process::id()));
+        let summary_path =
+            env::temp_dir().join(format!("rss_autogen_giscus_test_summary_{}", process::id()));
+        fs::write(&output_path, "previous=step\n").unwrap();
+        let _ = fs::remove_file(&summary_path);
+
+        let actions = GitHubActions {
+            output_path: Some(output_path.clone()),
+            summary_path: Some(summary_path.clone()),
+        };
+        let mut commands = Vec::new();
+        actions.publish(&report(), &mut commands).unwrap();
+
+        assert!(fs::read_to_string(&output_path)
+            .unwrap()
+            .starts_with("previous=step\ndiscussion-url="));
+        assert!(fs::read_to_string(&summary_path)
+            .unwrap()
+            .contains("| Post | Action | Discussion |"));
+        assert_eq!(String::from_utf8(commands).unwrap().lines().count(), 3);
+
+        fs::remove_file(output_path).unwrap();
+        fs::remove_file(summary_path).unwrap();
+    }
+}
@@ -0,0 +1,347 @@
+use std::fmt::Debug;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use crate::rest::github_rest_json;
+use crate::{Error, HttpClients};
+
+/// Supplies the token used to authenticate requests to the GitHub API.
+#[async_trait]
+pub trait TokenProvider: Debug + Send + Sync {
+    /// Returns the token to send in the `Authorization` header.
+    async fn token(&self, clients: &HttpClients) -> Result<String, Error>;
+
+    /// Discards the current token after GitHub rejected it with `401 Unauthorized`, so that the
+    /// next call to [`token`](TokenProvider::token) fetches a fresh one.
+    ///
+    /// Returns `false` if the token can't be refreshed, in which case the request isn't retried.
+    async fn invalidate(&self) -> bool {
+        false
+    }
+}
+
+/// A fixed token, typically from the `GITHUB_TOKEN` environment variable.
+pub struct StaticToken(pub String);
+
+#[async_trait]
+impl TokenProvider for StaticToken {
+    async fn token(&self, _clients: &HttpClients) -> Result<String, Error> {
+        Ok(self.0.clone())
+    }
+}
+
+impl Debug for StaticToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StaticToken(<redacted>)")
+    }
+}
+
+/// A token read from a file, such as a mounted secret. The file is read again whenever the token
+/// is rejected, so that rotated secrets are picked up.
+#[derive(Debug)]
+pub struct FileToken {
+    /// Path of the file containing the token. Surrounding whitespace is ignored.
+    pub path: PathBuf,
+    cached_token: Mutex<Option<String>>,
+}
+
+impl FileToken {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cached_token: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenProvider for FileToken {
+    async fn token(&self, _clients: &HttpClients) -> Result<String, Error> {
+        let mut cached_token = self.cached_token.lock().await;
+        if let Some(token) = cached_token.as_ref() {
+            return Ok(token.clone());
+        }
+
+        let token = fs::read_to_string(&self.path)
+            .map_err(|e| Error::Token(format!("Unable to read {}: {e}", self.path.display())))?
+            .trim()
+            .to_string();
+        *cached_token = Some(token.clone());
+        Ok(token)
+    }
+
+    async fn invalidate(&self) -> bool {
+        self.cached_token.lock().await.take();
+        true
+    }
+}
+
+/// A token printed to stdout by an external credential helper, run with `sh -c`. The command is
+/// run again whenever the token is rejected.
+#[derive(Debug)]
+pub struct CommandToken {
+    /// The shell command to run.
+    pub command: String,
+    cached_token: Mutex<Option<String>>,
+}
+
+impl CommandToken {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            cached_token: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenProvider for CommandToken {
+    async fn token(&self, _clients: &HttpClients) -> Result<String, Error> {
+        let mut cached_token = self.cached_token.lock().await;
+        if let Some(token) = cached_token.as_ref() {
+            return Ok(token.clone());
+        }
+
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .output()
+            .await
+            .map_err(|e| Error::Token(format!("Unable to run `{}`: {e}", self.command)))?;
+        if !output.status.success() {
+            return Err(Error::Token(format!(
+                "`{}` failed ({}): {}",
+                self.command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        *cached_token = Some(token.clone());
+        Ok(token)
+    }
+
+    async fn invalidate(&self) -> bool {
+        self.cached_token.lock().await.take();
+        true
+    }
+}
+
+/// Installation tokens are refreshed when they are this close to expiring, so that a token doesn't
+/// expire in the middle of a run.
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// Authentication as a [GitHub App](https://docs.github.com/en/apps/creating-github-apps/authenticating-with-a-github-app/about-authentication-with-a-github-app)
+/// installation, as an alternative to a long-lived personal access token.
+///
+/// The app's private key is used to sign a JWT, which is exchanged for an installation access
+/// token scoped to the comments repository. The token is cached, and refreshed automatically
+/// before it expires.
+pub struct GitHubApp {
+    /// The app ID, shown on the app's settings page.
+    pub app_id: String,
+
+    /// The installation ID for the comments repository. If not set, it is looked up from the
+    /// repository when the first token is requested.
+    pub installation_id: Option<u64>,
+
+    private_key: EncodingKey,
+    cached_token: Mutex<Option<InstallationToken>>,
+}
+
+/// Claims for the JWT used to authenticate as the app.
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Deserialize))]
+struct AppClaims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+#[derive(Deserialize)]
+struct Installation {
+    id: u64,
+}
+
+#[derive(Clone, Deserialize)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl GitHubApp {
+    /// Creates the app authentication from its ID and RSA private key, in PEM format.
+    pub fn new(
+        app_id: String,
+        private_key_pem: &[u8],
+        installation_id: Option<u64>,
+    ) -> jsonwebtoken::errors::Result<Self> {
+        Ok(Self {
+            app_id,
+            installation_id,
+            private_key: EncodingKey::from_rsa_pem(private_key_pem)?,
+            cached_token: Mutex::new(None),
+        })
+    }
+
+    /// Signs a short-lived JWT identifying the app.
+    fn jwt(&self) -> String {
+        let now = Utc::now().timestamp();
+        let claims = AppClaims {
+            // Allow for clock drift, as recommended by GitHub
+            iat: now - 60,
+            exp: now + 9 * 60,
+            iss: self.app_id.clone(),
+        };
+
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.private_key)
+            .expect("Unable to sign GitHub App JWT")
+    }
+
+    /// Requests a new installation access token, limited to the comments repository.
+    async fn installation_token(&self, clients: &HttpClients) -> Result<InstallationToken, Error> {
+        let jwt = self.jwt();
+        let installation_id = match self.installation_id {
+            Some(id) => id,
+            None => {
+                let installation: Installation = github_rest_json(
+                    clients
+                        .html
+                        .get(format!(
+                            "{}/repos/{}/{}/installation",
+                            clients.github_rest_url, clients.repo_owner, clients.repo_name
+                        ))
+                        .bearer_auth(&jwt),
+                )
+                .await?;
+                installation.id
+            }
+        };
+
+        github_rest_json(
+            clients
+                .html
+                .post(format!(
+                    "{}/app/installations/{installation_id}/access_tokens",
+                    clients.github_rest_url
+                ))
+                .bearer_auth(&jwt)
+                .json(&serde_json::json!({ "repositories": [clients.repo_name] })),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl TokenProvider for GitHubApp {
+    /// Returns an installation access token for the comments repository, requesting a new one if
+    /// the cached token is missing or about to expire.
+    async fn token(&self, clients: &HttpClients) -> Result<String, Error> {
+        let mut cached_token = self.cached_token.lock().await;
+        if let Some(token) = cached_token.as_ref() {
+            if token.expires_at - Utc::now() > chrono::Duration::from_std(REFRESH_MARGIN).unwrap() {
+                return Ok(token.token.clone());
+            }
+        }
+
+        let token = self.installation_token(clients).await?;
+        *cached_token = Some(token.clone());
+        Ok(token.token)
+    }
+
+    async fn invalidate(&self) -> bool {
+        self.cached_token.lock().await.take();
+        true
+    }
+}
+
+impl Debug for GitHubApp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GitHubApp")
+            .field("app_id", &self.app_id)
+            .field("installation_id", &self.installation_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use crate::auth::AppClaims;
+    use crate::{CommandToken, Error, FileToken, GitHubApp, HttpClients, TokenProvider};
+
+    /// A throwaway key pair, generated only for these tests.
+    const PRIVATE_KEY: &[u8] = include_bytes!("../tests/fixtures/github_app.pem");
+    const PUBLIC_KEY: &[u8] = include_bytes!("../tests/fixtures/github_app.pub.pem");
+
+    #[test]
+    fn test_app_jwt() {
+        let app = GitHubApp::new("123456".to_string(), PRIVATE_KEY, None).unwrap();
+
+        let claims = decode::<AppClaims>(
+            &app.jwt(),
+            &DecodingKey::from_rsa_pem(PUBLIC_KEY).unwrap(),
+            &Validation::new(Algorithm::RS256),
+        )
+        .unwrap()
+        .claims;
+
+        assert_eq!(claims.iss, "123456");
+        assert!(claims.exp - claims.iat <= 10 * 60);
+    }
+
+    #[test]
+    fn test_token_redacted() {
+        let clients = HttpClients::test_setup(true);
+        assert!(!format!("{clients:?}").contains("FAKE_TOKEN"));
+    }
+
+    #[test]
+    fn test_invalid_private_key() {
+        assert!(GitHubApp::new("123456".to_string(), b"not a key", Some(1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_token_reread() {
+        let clients = HttpClients::test_setup(true);
+        let path = env::temp_dir().join(format!("rss_autogen_giscus_test_token_{}", process::id()));
+        fs::write(&path, "first_token\n").unwrap();
+
+        let provider = FileToken::new(&path);
+        assert_eq!(provider.token(&clients).await.unwrap(), "first_token");
+
+        fs::write(&path, "second_token").unwrap();
+        assert_eq!(provider.token(&clients).await.unwrap(), "first_token");
+        assert!(provider.invalidate().await);
+        assert_eq!(provider.token(&clients).await.unwrap(), "second_token");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_command_token() {
+        let clients = HttpClients::test_setup(true);
+        let provider = CommandToken::new("echo '  helper_token  '");
+        assert_eq!(provider.token(&clients).await.unwrap(), "helper_token");
+
+        let failing = CommandToken::new("echo denied >&2; exit 1");
+        assert!(matches!(
+            failing.token(&clients).await,
+            Err(Error::Token(message)) if message.ends_with("denied")
+        ));
+    }
+}
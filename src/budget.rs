@@ -0,0 +1,93 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use gh_gql_schema::RateLimit;
+
+/// Tracks the GitHub GraphQL point budget, as reported by the `rateLimit` field of each query.
+///
+/// This is shared between clones of [`HttpClients`](crate::HttpClients), so that every query in
+/// a run counts against the same budget.
+#[derive(Debug, Default)]
+pub struct RateLimitBudget {
+    state: Mutex<BudgetState>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct BudgetState {
+    /// Points consumed by the queries in this run.
+    consumed: u64,
+
+    /// Points remaining in the current window, as of the last query.
+    remaining: Option<u64>,
+
+    /// When the current window resets.
+    reset_at: Option<DateTime<Utc>>,
+}
+
+impl RateLimitBudget {
+    /// Records the rate limit details returned with a query.
+    pub(crate) fn record(&self, rate_limit: &RateLimit) {
+        let mut state = self.state.lock().unwrap();
+        state.consumed += rate_limit.cost.max(0) as u64;
+        state.remaining = Some(rate_limit.remaining.max(0) as u64);
+        state.reset_at = rate_limit.reset_at.0.parse().ok();
+    }
+
+    /// Forgets the remaining points after waiting for the window to reset.
+    pub(crate) fn window_reset(&self) {
+        self.state.lock().unwrap().remaining = None;
+    }
+
+    /// Points consumed by the queries in this run.
+    pub fn consumed(&self) -> u64 {
+        self.state.lock().unwrap().consumed
+    }
+
+    /// Points remaining in the current window, if any query has reported it yet.
+    pub fn remaining(&self) -> Option<u64> {
+        self.state.lock().unwrap().remaining
+    }
+
+    /// If no more than `reserve` points remain, returns when the current window resets.
+    pub(crate) fn exhausted_until(&self, reserve: u64) -> Option<DateTime<Utc>> {
+        let state = *self.state.lock().unwrap();
+        match state.remaining {
+            Some(remaining) if remaining <= reserve => {
+                Some(state.reset_at.unwrap_or_else(Utc::now))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gh_gql_schema::{DateTime, RateLimit};
+
+    use crate::RateLimitBudget;
+
+    #[test]
+    fn test_budget_reserve() {
+        let budget = RateLimitBudget::default();
+        assert_eq!(budget.exhausted_until(100), None);
+
+        for remaining in [150, 99] {
+            budget.record(&RateLimit {
+                cost: 1,
+                remaining,
+                reset_at: DateTime("2023-06-13T20:00:00Z".to_string()),
+            });
+        }
+
+        assert_eq!(budget.consumed(), 2);
+        assert_eq!(budget.remaining(), Some(99));
+        assert_eq!(
+            budget.exhausted_until(100),
+            Some("2023-06-13T20:00:00Z".parse().unwrap())
+        );
+        assert_eq!(budget.exhausted_until(50), None);
+
+        budget.window_reset();
+        assert_eq!(budget.exhausted_until(100), None);
+    }
+}
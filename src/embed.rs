@@ -0,0 +1,486 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use ring::digest;
+use tracing::{instrument, warn};
+
+use crate::gql::get_repo_id;
+use crate::hooks::to_hex;
+use crate::routing::{category_id, post_category_id};
+use crate::{Error, HttpClients, Post};
+
+/// The Giscus mapping used by this crate. Discussions are titled with the post's path, so Giscus
+/// must map pages to discussions by their pathname.
+pub const MAPPING: &str = "pathname";
+
+/// Start of the hash Giscus looks for in discussion bodies when [strict](HttpClients::giscus_strict)
+/// title matching is enabled.
+pub(crate) const GISCUS_HASH: &str = "<!-- sha1:";
+
+/// The template the Giscus snippet is rendered for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SnippetFormat {
+    /// A plain `<script>` tag.
+    #[default]
+    Html,
+
+    /// A Hugo partial, such as `layouts/partials/giscus.html`, skipped for pages with
+    /// `disableComments` in their front matter.
+    Hugo,
+
+    /// A Jekyll include, such as `_includes/giscus.html`, skipped for pages with
+    /// `comments: false` in their front matter.
+    Jekyll,
+}
+
+impl FromStr for SnippetFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(Self::Html),
+            "hugo" => Ok(Self::Hugo),
+            "jekyll" => Ok(Self::Jekyll),
+            _ => Err(format!(
+                "Unknown snippet format {s}, expected html, hugo or jekyll"
+            )),
+        }
+    }
+}
+
+/// Options for [`giscus_snippet`], matching the settings on <https://giscus.app>.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetOptions {
+    /// Template the snippet is rendered for.
+    pub format: SnippetFormat,
+
+    /// Category the discussions are loaded from, instead of the
+    /// [discussion category](HttpClients::discussion_category).
+    pub category: Option<String>,
+
+    /// Giscus theme, such as `light`, `dark` or `preferred_color_scheme`.
+    pub theme: String,
+
+    /// Language of the Giscus interface, such as `en`.
+    pub lang: String,
+
+    /// Whether reactions to the discussion are shown above the comments.
+    pub reactions_enabled: bool,
+
+    /// Whether the comment box is shown above the comments, rather than below them.
+    pub input_top: bool,
+
+    /// Whether Giscus is only loaded when the reader scrolls to the comments.
+    pub lazy: bool,
+}
+
+impl Default for SnippetOptions {
+    fn default() -> Self {
+        Self {
+            format: SnippetFormat::Html,
+            category: None,
+            theme: "preferred_color_scheme".to_string(),
+            lang: "en".to_string(),
+            reactions_enabled: true,
+            input_top: false,
+            lazy: false,
+        }
+    }
+}
+
+/// The repository and category settings of a Giscus embed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GiscusEmbed {
+    /// Repository in `owner/name` format.
+    pub repo: String,
+
+    /// GraphQL node ID of the repository.
+    pub repo_id: String,
+
+    /// Name of the discussion category.
+    pub category: String,
+
+    /// GraphQL node ID of the discussion category.
+    pub category_id: String,
+
+    /// Whether Giscus uses [strict](HttpClients::giscus_strict) title matching.
+    pub strict: bool,
+}
+
+impl GiscusEmbed {
+    /// Renders the Giscus `<script>` tag in the requested format.
+    pub fn render(&self, options: &SnippetOptions) -> String {
+        let flag = |enabled: bool| if enabled { "1" } else { "0" };
+        let mut attributes = vec![
+            ("data-repo", self.repo.as_str()),
+            ("data-repo-id", self.repo_id.as_str()),
+            ("data-category", self.category.as_str()),
+            ("data-category-id", self.category_id.as_str()),
+            ("data-mapping", MAPPING),
+            ("data-strict", flag(self.strict)),
+            ("data-reactions-enabled", flag(options.reactions_enabled)),
+            ("data-emit-metadata", "0"),
+            (
+                "data-input-position",
+                if options.input_top { "top" } else { "bottom" },
+            ),
+            ("data-theme", options.theme.as_str()),
+            ("data-lang", options.lang.as_str()),
+        ];
+        if options.lazy {
+            attributes.push(("data-loading", "lazy"));
+        }
+
+        let mut script = String::from("<script src=\"https://giscus.app/client.js\"\n");
+        for (name, value) in attributes {
+            script.push_str(&format!("        {name}=\"{}\"\n", escape_attr(value)));
+        }
+        script.push_str("        crossorigin=\"anonymous\"\n        async>\n</script>\n");
+
+        match options.format {
+            SnippetFormat::Html => script,
+            SnippetFormat::Hugo => {
+                format!("{{{{- if not .Params.disableComments }}}}\n{script}{{{{- end }}}}\n")
+            }
+            SnippetFormat::Jekyll => {
+                format!("{{% if page.comments != false %}}\n{script}{{% endif %}}\n")
+            }
+        }
+    }
+}
+
+/// What is done when a post's page embeds Giscus with settings that don't match the
+/// configuration (see [`check_embed`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmbedCheck {
+    /// The page isn't checked.
+    Off,
+
+    /// The mismatches are reported as warnings.
+    #[default]
+    Warn,
+
+    /// The post isn't processed, failing with [`Error::EmbedMismatch`].
+    Refuse,
+}
+
+impl FromStr for EmbedCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "refuse" => Ok(Self::Refuse),
+            _ => Err(format!(
+                "Unknown embed check {s}, expected off, warn or refuse"
+            )),
+        }
+    }
+}
+
+/// The settings of a Giscus `<script>` tag found on a post's page, from its `data-*` attributes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GiscusScript {
+    /// `data-repo`, the repository in `owner/name` format.
+    pub repo: Option<String>,
+
+    /// `data-repo-id`, the GraphQL node ID of the repository.
+    pub repo_id: Option<String>,
+
+    /// `data-category`, the name of the discussion category.
+    pub category: Option<String>,
+
+    /// `data-category-id`, the GraphQL node ID of the discussion category.
+    pub category_id: Option<String>,
+
+    /// `data-mapping`, how pages are mapped to discussions (see [`MAPPING`]).
+    pub mapping: Option<String>,
+
+    /// `data-strict`, `1` if Giscus uses strict title matching.
+    pub strict: Option<String>,
+}
+
+impl GiscusScript {
+    /// Describes each setting of the script that doesn't match the `expected` embed, which would
+    /// make Giscus load a different discussion than the one created by this crate.
+    pub fn mismatches(&self, expected: &GiscusEmbed) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        let mut check = |name: &str, actual: Option<&str>, expected: &str| {
+            if let Some(actual) = actual.filter(|actual| !actual.eq_ignore_ascii_case(expected)) {
+                mismatches.push(format!(
+                    "The page's Giscus {name} is {actual}, but {expected} is configured"
+                ));
+            }
+        };
+
+        check("data-repo", self.repo.as_deref(), &expected.repo);
+        check("data-repo-id", self.repo_id.as_deref(), &expected.repo_id);
+        match &self.category_id {
+            Some(_) => check(
+                "data-category-id",
+                self.category_id.as_deref(),
+                &expected.category_id,
+            ),
+            None => check(
+                "data-category",
+                self.category.as_deref(),
+                &expected.category,
+            ),
+        }
+        check("data-mapping", self.mapping.as_deref(), MAPPING);
+        check(
+            "data-strict",
+            Some(self.strict.as_deref().unwrap_or("0")),
+            if expected.strict { "1" } else { "0" },
+        );
+        mismatches
+    }
+}
+
+fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Resolves the repository and category IDs, and renders the Giscus embed snippet consistent with
+/// how this crate creates discussions.
+pub async fn giscus_snippet(
+    clients: Arc<HttpClients>,
+    options: &SnippetOptions,
+) -> Result<String, Error> {
+    if clients.github_server_url != "https://github.com" {
+        warn!(
+            server = clients.github_server_url,
+            "giscus.app only supports github.com, the snippet needs a self-hosted Giscus instance"
+        );
+    }
+
+    let category = options
+        .category
+        .clone()
+        .unwrap_or_else(|| clients.discussion_category.clone());
+    let embed = GiscusEmbed {
+        repo: format!("{}/{}", clients.repo_owner, clients.repo_name),
+        repo_id: get_repo_id(Arc::clone(&clients)).await?.into_inner(),
+        category_id: category_id(Arc::clone(&clients), &category)
+            .await?
+            .inner()
+            .to_string(),
+        category,
+        strict: clients.giscus_strict,
+    };
+    Ok(embed.render(options))
+}
+
+/// Compares the Giscus script on the post's page with the configuration, according to the
+/// [embed check](HttpClients::embed_check). Pages without a Giscus script aren't checked, as the
+/// embed may be added by other scripts.
+///
+/// Returns the mismatches found, or [`Error::EmbedMismatch`] if they should stop the post from
+/// being processed.
+#[instrument(skip_all, fields(post = %post.url))]
+pub async fn check_embed(clients: Arc<HttpClients>, post: &Post) -> Result<Vec<String>, Error> {
+    let script = match (&post.giscus, clients.embed_check) {
+        (_, EmbedCheck::Off) | (None, _) => return Ok(Vec::new()),
+        (Some(script), _) => script,
+    };
+
+    let expected = GiscusEmbed {
+        repo: format!("{}/{}", clients.repo_owner, clients.repo_name),
+        repo_id: match &script.repo_id {
+            Some(_) => get_repo_id(Arc::clone(&clients)).await?.into_inner(),
+            None => String::new(),
+        },
+        category: clients
+            .category_routes
+            .category_for(post, &clients.discussion_category)
+            .to_string(),
+        category_id: match &script.category_id {
+            Some(_) => post_category_id(Arc::clone(&clients), post)
+                .await?
+                .inner()
+                .to_string(),
+            None => String::new(),
+        },
+        strict: clients.giscus_strict,
+    };
+    let mismatches = script.mismatches(&expected);
+    if clients.embed_check == EmbedCheck::Refuse && !mismatches.is_empty() {
+        return Err(Error::EmbedMismatch(mismatches));
+    }
+    for mismatch in &mismatches {
+        warn!("{mismatch}");
+    }
+    Ok(mismatches)
+}
+
+/// The term Giscus searches for with the [`MAPPING`] of the page at `path`: the path without its
+/// leading slash and file extension.
+pub(crate) fn pathname_term(path: &str) -> &str {
+    if path.len() < 2 {
+        return "index";
+    }
+    let term = &path[1..];
+    match term.rfind('.') {
+        Some(dot)
+            if dot + 1 < term.len()
+                && term[dot + 1..]
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+        {
+            &term[..dot]
+        }
+        _ => term,
+    }
+}
+
+/// Whether the discussion title is exactly the page's path, as titled by this crate, or the term
+/// Giscus searches for.
+pub(crate) fn is_mapping_title(title: &str, path: &str) -> bool {
+    title == path || title == pathname_term(path)
+}
+
+/// The hash Giscus adds to discussion bodies for strict matching, for the page at `path`.
+pub(crate) fn strict_hash(path: &str) -> String {
+    let hash = digest::digest(
+        &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        pathname_term(path).as_bytes(),
+    );
+    format!("{GISCUS_HASH} {} -->", to_hex(hash.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::embed::{
+        is_mapping_title, pathname_term, strict_hash, GiscusEmbed, GiscusScript, SnippetFormat,
+        SnippetOptions,
+    };
+
+    fn embed() -> GiscusEmbed {
+        GiscusEmbed {
+            repo: "team-role-org-testing/team-role-org-testing.github.io".to_string(),
+            repo_id: "R_kgDOJSVgjQ".to_string(),
+            category: "Q&A".to_string(),
+            category_id: "DIC_kwDOJSVgjc4CVgpt".to_string(),
+            strict: true,
+        }
+    }
+
+    #[test]
+    fn test_render_html() {
+        assert_eq!(
+            embed().render(&SnippetOptions::default()),
+            r#"<script src="https://giscus.app/client.js"
+        data-repo="team-role-org-testing/team-role-org-testing.github.io"
+        data-repo-id="R_kgDOJSVgjQ"
+        data-category="Q&amp;A"
+        data-category-id="DIC_kwDOJSVgjc4CVgpt"
+        data-mapping="pathname"
+        data-strict="1"
+        data-reactions-enabled="1"
+        data-emit-metadata="0"
+        data-input-position="bottom"
+        data-theme="preferred_color_scheme"
+        data-lang="en"
+        crossorigin="anonymous"
+        async>
+</script>
+"#
+        );
+    }
+
+    #[test]
+    fn test_render_templates() {
+        let hugo = embed().render(&SnippetOptions {
+            format: SnippetFormat::Hugo,
+            lazy: true,
+            ..SnippetOptions::default()
+        });
+        assert!(hugo.starts_with("{{- if not .Params.disableComments }}\n<script"));
+        assert!(hugo.ends_with("</script>\n{{- end }}\n"));
+        assert!(hugo.contains("data-loading=\"lazy\""));
+
+        let jekyll = embed().render(&SnippetOptions {
+            format: SnippetFormat::Jekyll,
+            ..SnippetOptions::default()
+        });
+        assert!(jekyll.starts_with("{% if page.comments != false %}\n<script"));
+        assert!(jekyll.ends_with("</script>\n{% endif %}\n"));
+    }
+
+    #[test]
+    fn test_pathname_term() {
+        assert_eq!(pathname_term("/"), "index");
+        assert_eq!(pathname_term("/posts/hello/"), "posts/hello/");
+        assert_eq!(
+            pathname_term("/jekyll/update/2023/04/03/welcome-to-jekyll.html"),
+            "jekyll/update/2023/04/03/welcome-to-jekyll"
+        );
+        assert_eq!(pathname_term("/releases/v1.2/"), "releases/v1.2/");
+    }
+
+    #[test]
+    fn test_mapping_title() {
+        assert!(is_mapping_title("/posts/hello/", "/posts/hello/"));
+        assert!(is_mapping_title("posts/hello", "/posts/hello.html"));
+        assert!(!is_mapping_title("/posts", "/posts/hello/"));
+        assert!(!is_mapping_title("hello", "/posts/hello/"));
+    }
+
+    #[test]
+    fn test_strict_hash() {
+        assert_eq!(
+            strict_hash("/index.html"),
+            "<!-- sha1: e540cdd1328b2b21e29a95405c301b9313b7c346 -->"
+        );
+        assert_eq!(
+            strict_hash("/posts/hello/"),
+            "<!-- sha1: 2ffba58dbd69d5b6f4828c742d3873779769e688 -->"
+        );
+    }
+
+    #[test]
+    fn test_script_mismatches() {
+        let script = GiscusScript {
+            repo: Some("Team-Role-Org-Testing/team-role-org-testing.github.io".to_string()),
+            repo_id: Some("R_kgDOJSVgjQ".to_string()),
+            category: Some("Q&A".to_string()),
+            category_id: Some("DIC_kwDOJSVgjc4CVgpt".to_string()),
+            mapping: Some("pathname".to_string()),
+            strict: Some("1".to_string()),
+        };
+        assert!(script.mismatches(&embed()).is_empty());
+
+        let script = GiscusScript {
+            category_id: Some("DIC_other".to_string()),
+            mapping: Some("og:title".to_string()),
+            strict: None,
+            ..script
+        };
+        assert_eq!(
+            script.mismatches(&embed()),
+            [
+                "The page's Giscus data-category-id is DIC_other, but DIC_kwDOJSVgjc4CVgpt is configured",
+                "The page's Giscus data-mapping is og:title, but pathname is configured",
+                "The page's Giscus data-strict is 0, but 1 is configured",
+            ]
+        );
+
+        // The category name is compared when the page doesn't set the category's ID
+        let script = GiscusScript {
+            category: Some("Blogs".to_string()),
+            category_id: None,
+            mapping: None,
+            strict: Some("1".to_string()),
+            ..GiscusScript::default()
+        };
+        assert_eq!(
+            script.mismatches(&embed()),
+            ["The page's Giscus data-category is Blogs, but Q&A is configured"]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt::{Display, Formatter};
+
+use chrono::{DateTime, Utc};
+use cynic::http::CynicReqwestError;
+
+/// The kind of GitHub rate limit that was hit.
+///
+/// See [the GitHub documentation](https://docs.github.com/en/rest/overview/resources-in-the-rest-api#rate-limiting)
+/// for the differences between the two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKind {
+    /// The hourly request or point budget for the token has been exhausted.
+    Primary,
+
+    /// Too many requests were made in a short period, or too many concurrent requests.
+    Secondary,
+}
+
+/// Errors returned while creating the discussion.
+#[derive(Debug)]
+pub enum Error {
+    /// An error occurred while sending a request, or GitHub returned an unexpected response.
+    Request(CynicReqwestError),
+
+    /// GitHub rate limited the request, and waiting for it to reset would exceed
+    /// [`max_rate_limit_wait`](crate::HttpClients::max_rate_limit_wait).
+    RateLimited {
+        /// Which rate limit was hit.
+        kind: RateLimitKind,
+
+        /// When the request can be retried.
+        until: DateTime<Utc>,
+    },
+
+    /// GitHub rejected the token, and it couldn't be refreshed by the
+    /// [token provider](crate::HttpClients::token_provider).
+    Unauthorized(String),
+
+    /// The [token provider](crate::HttpClients::token_provider) couldn't supply a token, such as
+    /// when its file can't be read or its command fails.
+    Token(String),
+
+    /// The GitHub Enterprise Server release is older than [`MIN_GHES_VERSION`](crate::MIN_GHES_VERSION).
+    UnsupportedServer(String),
+
+    /// The GitHub server's GraphQL schema lacks fields used by this crate, typically because it is
+    /// an older GitHub Enterprise Server release.
+    UnsupportedSchema(String),
+
+    /// The changed content files couldn't be listed from the push event or git range of the
+    /// [post source](crate::HttpClients::post_source).
+    ChangedFiles(String),
+
+    /// The discussion category doesn't exist in the repository.
+    CategoryNotFound {
+        /// Name of the category.
+        category: String,
+
+        /// The repository, as `owner/name`.
+        repo: String,
+    },
+
+    /// The [preflight checks](crate::preflight) found problems with the token or repository.
+    Preflight(Vec<String>),
+
+    /// The post's page embeds Giscus with settings that don't match the configuration (see
+    /// [`check_embed`](crate::check_embed)).
+    EmbedMismatch(Vec<String>),
+
+    /// Processing a post failed, with the errors recorded in its [report](crate::PostReport).
+    PostFailed {
+        /// Link to the blog post.
+        url: String,
+
+        /// The errors that stopped the post from being processed.
+        errors: Vec<String>,
+    },
+
+    /// GitHub returned errors for a GraphQL operation, such as a mutation it refused to apply.
+    GraphQl(String),
+
+    /// The request still failed after the maximum number of attempts allowed by the
+    /// [retry policy](crate::HttpClients::retry_policy).
+    RetriesExhausted {
+        /// Name of the GraphQL operation.
+        operation: String,
+
+        /// Number of attempts made.
+        attempts: u32,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Request(e) => write!(f, "{e}"),
+            Self::RateLimited { kind, until } => {
+                write!(f, "{kind:?} rate limit reached, retry after {until}")
+            }
+            Self::Unauthorized(body) => write!(f, "Invalid authentication token: {body}"),
+            Self::Token(message) => write!(f, "Unable to get an authentication token: {message}"),
+            Self::UnsupportedServer(version) => write!(
+                f,
+                "GitHub Enterprise Server {version} is not supported, discussions require {}.{} or later",
+                crate::MIN_GHES_VERSION.0,
+                crate::MIN_GHES_VERSION.1
+            ),
+            Self::UnsupportedSchema(message) => write!(
+                f,
+                "The GitHub GraphQL API doesn't support this operation, it may be an older GitHub Enterprise Server release: {message}"
+            ),
+            Self::ChangedFiles(message) => write!(f, "Unable to list changed posts: {message}"),
+            Self::CategoryNotFound { category, repo } => {
+                write!(f, "Category {category} was not present in repository {repo}")
+            }
+            Self::Preflight(problems) => {
+                writeln!(f, "Preflight checks failed:")?;
+                for problem in problems {
+                    writeln!(f, "- {problem}")?;
+                }
+                Ok(())
+            }
+            Self::EmbedMismatch(mismatches) => write!(
+                f,
+                "The page's Giscus embed doesn't match the configuration: {}",
+                mismatches.join("; ")
+            ),
+            Self::PostFailed { url, errors } => {
+                write!(f, "Unable to process post {url}: {}", errors.join("; "))
+            }
+            Self::GraphQl(message) => write!(f, "GitHub returned GraphQL errors: {message}"),
+            Self::RetriesExhausted {
+                operation,
+                attempts,
+            } => write!(
+                f,
+                "Exceeded maximum of {attempts} attempts while executing {operation}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Request(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<CynicReqwestError> for Error {
+    fn from(value: CynicReqwestError) -> Self {
+        Self::Request(value)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(value: reqwest::Error) -> Self {
+        Self::Request(CynicReqwestError::ReqwestError(value))
+    }
+}
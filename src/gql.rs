@@ -1,84 +1,427 @@
+use std::ops::ControlFlow;
 use std::sync::Arc;
 use std::time::Duration;
 
+use chrono::{DateTime, TimeZone, Utc};
 use cynic::http::CynicReqwestError;
-use cynic::schema::QueryRoot;
-use cynic::{http::ReqwestExt, GraphQlResponse, Id, Operation, QueryFragment, QueryVariables};
+use cynic::schema::{MutationRoot, QueryRoot};
+use cynic::{GraphQlResponse, Id, Operation, QueryFragment, QueryVariables};
+use reqwest::header::HeaderMap;
 use reqwest::StatusCode;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use tokio::time::sleep;
+use tracing::{debug, info, instrument, warn, Span};
 
-use crate::{HttpClients, Post};
+use crate::embed::{is_mapping_title, strict_hash};
+use crate::moved::id_marker;
+use crate::routing::{category_id, DuplicateSearch};
+use crate::{Error, HttpClients, Post, RateLimitKind};
 use gh_gql_schema::{
     CategoryQuery, CategoryQueryVariables, CreateCommentsDiscussion,
-    CreateCommentsDiscussionVariables, DiscussionExists, DiscussionExistsVariables, RepoIdQuery,
-    RepoIdQueryVariables,
+    CreateCommentsDiscussionVariables, Discussion, DiscussionComments, DiscussionCommentsVariables,
+    DiscussionExists, DiscussionExistsVariables, RateLimited, RepoIdQuery, RepoIdQueryVariables,
 };
 
-/// Executes a GraphQL call to the GitHub API, respecting rate limits.
-///
-/// To support rate limits, `query_vars` must also implement [`Clone`].
+/// Executes a GraphQL call to the GitHub API, respecting rate limits and the
+/// [retry policy](HttpClients::retry_policy).
 pub async fn github_gql_query<T, Variables>(
     clients: Arc<HttpClients>,
     query_vars: Variables,
-) -> Result<GraphQlResponse<T>, CynicReqwestError>
+) -> Result<GraphQlResponse<T>, Error>
 where
-    Variables: QueryVariables + Serialize + Clone,
-    T: QueryFragment<VariablesFields = Variables::Fields> + DeserializeOwned + 'static,
+    Variables: QueryVariables + Serialize,
+    T: QueryFragment<VariablesFields = Variables::Fields>
+        + RateLimited
+        + DeserializeOwned
+        + 'static,
     T::SchemaType: QueryRoot,
 {
     use cynic::QueryBuilder;
 
-    let query_attempts = vec![query_vars.clone(); 5];
-    let mut attempt = 0;
-    for vars in query_attempts {
-        attempt += 1;
-        let resp = clients
-            .gql
-            .post(&clients.github_gql_url)
-            .run_graphql(T::build(vars))
-            .await;
-
-        if let Err(CynicReqwestError::ErrorResponse(err_status, err_body)) = resp {
-            if err_body.contains("Server Error") {
-                gql_sleep(err_status, err_body, 30).await;
-                continue;
+    run_operation(clients, T::build(query_vars), true).await
+}
+
+/// Executes a GraphQL mutation on the GitHub API, in the same way as [`github_gql_query`].
+///
+/// Only use this for mutations that can safely be repeated, as a failed attempt may still have
+/// been applied by GitHub before it is retried.
+pub async fn github_gql_mutation<T, Variables>(
+    clients: Arc<HttpClients>,
+    mutation_vars: Variables,
+) -> Result<GraphQlResponse<T>, Error>
+where
+    Variables: QueryVariables + Serialize,
+    T: QueryFragment<VariablesFields = Variables::Fields>
+        + RateLimited
+        + DeserializeOwned
+        + 'static,
+    T::SchemaType: MutationRoot,
+{
+    use cynic::MutationBuilder;
+
+    run_operation(clients, T::build(mutation_vars), true).await
+}
+
+/// Executes a GraphQL mutation that mustn't be repeated, such as adding a comment.
+///
+/// The mutation is only retried if GitHub didn't receive it, or rejected it before applying it
+/// (for rate limits and expired tokens). Timeouts and server errors fail with
+/// [`Error::RetriesExhausted`] instead, as the mutation may have been applied.
+pub async fn github_gql_mutation_once<T, Variables>(
+    clients: Arc<HttpClients>,
+    mutation_vars: Variables,
+) -> Result<GraphQlResponse<T>, Error>
+where
+    Variables: QueryVariables + Serialize,
+    T: QueryFragment<VariablesFields = Variables::Fields>
+        + RateLimited
+        + DeserializeOwned
+        + 'static,
+    T::SchemaType: MutationRoot,
+{
+    use cynic::MutationBuilder;
+
+    run_operation(clients, T::build(mutation_vars), false).await
+}
+
+/// Sends the operation until it succeeds, or the retry policy is exhausted. Unless `repeatable`,
+/// attempts that may have been applied by GitHub aren't retried.
+#[instrument(name = "graphql", skip_all, fields(operation))]
+async fn run_operation<T, Variables>(
+    clients: Arc<HttpClients>,
+    operation: Operation<T, Variables>,
+    repeatable: bool,
+) -> Result<GraphQlResponse<T>, Error>
+where
+    Variables: Serialize,
+    T: RateLimited + DeserializeOwned + 'static,
+{
+    Span::current().record("operation", operation.operation_name.as_deref());
+    let max_attempts = clients.retry_policy.max_attempts;
+    let mut refreshed = false;
+    let mut attempts = 0;
+    while attempts < max_attempts {
+        attempts += 1;
+        match gql_attempt(&clients, &operation, attempts, &mut refreshed).await? {
+            Attempt::Done(resp) => return Ok(resp),
+            Attempt::Retry {
+                reason,
+                maybe_applied: true,
+                ..
+            } if !repeatable => {
+                warn!(reason, "Request failed, and may have been applied");
+                break;
             }
-            match err_status.as_u16() {
-                403 => {
-                    gql_sleep(err_status, err_body, 5 * (2_u64).pow(attempt)).await;
-                    continue;
-                } // Rate limit reached
-                401 => panic!("Invalid authentication tokens:\n{:#?}", clients.gql),
-                400..=599 => {
-                    gql_sleep(err_status, err_body, 30).await;
-                    continue;
-                }
-                300..=399 => panic!(
-                    "Unexpected redirection response ({}): {}",
-                    err_status, err_body
-                ),
-                _ => panic!("Unhandled HTTP status code ({}): {}", err_status, err_body),
+            Attempt::Retry { reason, wait, .. } if attempts < max_attempts => {
+                gql_sleep(&reason, wait).await
             }
-        } else {
-            return resp;
+            Attempt::Retry { reason, .. } => warn!(reason, "Request failed"),
         }
     }
 
-    panic!(
-        "Exceeded maximum of 5 attempts while executing {}",
-        T::build(query_vars).operation_name.unwrap()
-    );
+    Err(Error::RetriesExhausted {
+        operation: operation.operation_name.unwrap_or_default().into_owned(),
+        attempts,
+    })
+}
+
+/// Result of the [`CreateCommentsDiscussion`] mutation.
+pub enum CreateResult {
+    /// The mutation completed, and its response was received.
+    Created(GraphQlResponse<CreateCommentsDiscussion>),
+
+    /// A failed attempt was applied by GitHub anyway, creating this discussion.
+    Existing(Discussion),
+}
+
+/// Executes the mutation to create a discussion, following the
+/// [retry policy](HttpClients::retry_policy).
+///
+/// A request that times out or fails with a server error may still have been applied by GitHub.
+/// Before each retry, [`discussion_exists`] is checked again so that a duplicate discussion isn't
+/// created.
+#[instrument(
+    name = "graphql",
+    skip_all,
+    fields(operation = "CreateCommentsDiscussion")
+)]
+pub async fn run_create_discussion(
+    clients: Arc<HttpClients>,
+    post: Arc<Post>,
+    cat_id: Arc<Id>,
+    operation: Operation<CreateCommentsDiscussion, CreateCommentsDiscussionVariables>,
+) -> Result<CreateResult, Error> {
+    let max_attempts = clients.retry_policy.max_attempts;
+    let mut refreshed = false;
+    for attempt in 1..=max_attempts {
+        if attempt > 1 {
+            if let Some(discussion) =
+                discussion_exists(Arc::clone(&clients), Arc::clone(&post), Arc::clone(&cat_id))
+                    .await?
+            {
+                return Ok(CreateResult::Existing(discussion));
+            }
+        }
+
+        match gql_attempt(&clients, &operation, attempt, &mut refreshed).await? {
+            Attempt::Done(resp) => return Ok(CreateResult::Created(resp)),
+            Attempt::Retry { reason, wait, .. } if attempt < max_attempts => {
+                gql_sleep(&reason, wait).await
+            }
+            Attempt::Retry { reason, .. } => warn!(reason, "Request failed"),
+        }
+    }
+
+    Err(Error::RetriesExhausted {
+        operation: operation.operation_name.unwrap_or_default().into_owned(),
+        attempts: max_attempts,
+    })
+}
+
+/// Outcome of a single attempt at a GraphQL request.
+enum Attempt<T> {
+    /// A response was received, which may still contain GraphQL errors.
+    Done(GraphQlResponse<T>),
+
+    /// The request failed, and may be retried after waiting.
+    Retry {
+        reason: String,
+        wait: Duration,
+
+        /// Whether GitHub may have applied the request despite the failure, such as after a
+        /// timeout or server error.
+        maybe_applied: bool,
+    },
+}
+
+/// Sends a GraphQL operation once, and determines whether it should be retried.
+///
+/// A rejected token is refreshed once per operation, tracked by `refreshed`, so that a token that
+/// stays invalid fails with [`Error::Unauthorized`].
+async fn gql_attempt<T, Variables>(
+    clients: &HttpClients,
+    operation: &Operation<T, Variables>,
+    attempt: u32,
+    refreshed: &mut bool,
+) -> Result<Attempt<T>, Error>
+where
+    Variables: Serialize,
+    T: RateLimited + DeserializeOwned + 'static,
+{
+    budget_sleep(clients).await?;
+
+    let resp = match clients
+        .gql
+        .post(&clients.github_gql_url)
+        .bearer_auth(clients.token_provider.token(clients).await?)
+        .json(operation)
+        .send()
+        .await
+    {
+        Ok(resp) => resp,
+        Err(e) if e.is_timeout() || e.is_connect() => {
+            return Ok(Attempt::Retry {
+                reason: e.to_string(),
+                wait: clients.retry_policy.delay(attempt),
+                maybe_applied: e.is_timeout(),
+            })
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let status = resp.status();
+    let rate_limit = RateLimitHeaders::from(resp.headers());
+    let body = resp.text().await?;
+
+    if let Some((kind, wait)) = rate_limit.wait_time(status, &body, attempt) {
+        if wait > clients.max_rate_limit_wait {
+            return Err(Error::RateLimited {
+                kind,
+                until: Utc::now() + chrono::Duration::from_std(wait).unwrap(),
+            });
+        }
+        return Ok(Attempt::Retry {
+            reason: format!("{kind:?} rate limit, {status}: {body}"),
+            wait,
+            maybe_applied: false,
+        });
+    }
+
+    if status.is_success() {
+        let gql_resp: GraphQlResponse<T> = serde_json::from_str(&body)
+            .map_err(|_| CynicReqwestError::ErrorResponse(status, body))?;
+        if let Some(rate_limit) = gql_resp.data.as_ref().and_then(|d| d.rate_limit()) {
+            clients.rate_limit_budget.record(rate_limit);
+        }
+        if let Some(message) = unsupported_schema(&gql_resp) {
+            return Err(Error::UnsupportedSchema(message));
+        }
+        return Ok(Attempt::Done(gql_resp));
+    }
+
+    if clients.retry_policy.is_retryable(status) {
+        return Ok(Attempt::Retry {
+            reason: format!("{status}: {body}"),
+            wait: clients.retry_policy.delay(attempt),
+            maybe_applied: true,
+        });
+    }
+    match status.as_u16() {
+        401 if !*refreshed && clients.token_provider.invalidate().await => {
+            *refreshed = true;
+            Ok(Attempt::Retry {
+                reason: format!("{status}, refreshing token: {body}"),
+                wait: Duration::ZERO,
+                maybe_applied: false,
+            })
+        }
+        401 => Err(Error::Unauthorized(body)),
+        // Includes redirections, such as from a GitHub Enterprise Server URL missing its API path
+        _ => Err(CynicReqwestError::ErrorResponse(status, body).into()),
+    }
+}
+
+/// Converts the GraphQL errors in a response, if any, into an [`Error::GraphQl`].
+pub(crate) fn check_errors<T>(resp: &GraphQlResponse<T>) -> Result<(), Error> {
+    match resp.errors.as_deref() {
+        None | Some([]) => Ok(()),
+        Some(errors) => Err(Error::GraphQl(
+            errors
+                .iter()
+                .map(|e| e.message.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )),
+    }
+}
+
+/// Checks a response for errors caused by fields missing from the server's schema, as happens
+/// when GitHub Enterprise Server is older than the bundled schema.
+fn unsupported_schema<T>(resp: &GraphQlResponse<T>) -> Option<String> {
+    let missing_fields: Vec<&str> = resp
+        .errors
+        .iter()
+        .flatten()
+        .map(|e| e.message.as_str())
+        .filter(|message| message.contains("doesn't exist on type"))
+        .collect();
+
+    match missing_fields.is_empty() {
+        true => None,
+        false => Some(missing_fields.join("\n")),
+    }
+}
+
+/// Rate limit details sent by GitHub in the response headers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct RateLimitHeaders {
+    /// Requests (or points, for GraphQL) remaining in the current window, from `x-ratelimit-remaining`.
+    remaining: Option<u64>,
+
+    /// When the current window resets, from `x-ratelimit-reset`.
+    reset: Option<DateTime<Utc>>,
+
+    /// How long to wait before retrying, from `retry-after`.
+    retry_after: Option<Duration>,
 }
 
-/// Sleep for a period of time upon receiving a non-200 status code from [`github_gql_query`].
-async fn gql_sleep(status: StatusCode, body: String, sleep_secs: u64) {
-    eprintln!(
-        "Request failed ({}): {}\nSleeping for {} seconds...",
-        status, body, sleep_secs
+impl From<&HeaderMap> for RateLimitHeaders {
+    fn from(headers: &HeaderMap) -> Self {
+        let number = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+
+        Self {
+            remaining: number("x-ratelimit-remaining"),
+            reset: number("x-ratelimit-reset")
+                .and_then(|secs| Utc.timestamp_opt(secs as i64, 0).single()),
+            retry_after: number("retry-after").map(Duration::from_secs),
+        }
+    }
+}
+
+impl RateLimitHeaders {
+    /// Determines whether a response was rate limited, and how long to wait before retrying.
+    ///
+    /// Secondary rate limits that don't include a `retry-after` header are retried after
+    /// an exponential backoff starting at one minute, as recommended by GitHub.
+    fn wait_time(
+        &self,
+        status: StatusCode,
+        body: &str,
+        attempt: u32,
+    ) -> Option<(RateLimitKind, Duration)> {
+        let limited_status = matches!(
+            status,
+            StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+        );
+
+        if limited_status {
+            if let Some(retry_after) = self.retry_after {
+                return Some((RateLimitKind::Secondary, retry_after));
+            }
+        }
+
+        // GraphQL reports an exhausted primary limit with a 200 status and a `RATE_LIMITED` error
+        if self.remaining == Some(0) && (limited_status || body.contains("RATE_LIMITED")) {
+            let until_reset = self
+                .reset
+                .and_then(|reset| (reset - Utc::now()).to_std().ok())
+                .unwrap_or_default();
+            return Some((RateLimitKind::Primary, until_reset + Duration::from_secs(1)));
+        }
+
+        if status == StatusCode::TOO_MANY_REQUESTS
+            || (limited_status && body.contains("secondary rate limit"))
+        {
+            return Some((
+                RateLimitKind::Secondary,
+                Duration::from_secs(60 * 2_u64.pow(attempt - 1)),
+            ));
+        }
+
+        None
+    }
+}
+
+/// Waits for the GraphQL rate limit window to reset, if the
+/// [remaining budget](HttpClients::rate_limit_budget) has reached the
+/// [reserve](HttpClients::rate_limit_reserve).
+async fn budget_sleep(clients: &HttpClients) -> Result<(), Error> {
+    if let Some(reset_at) = clients
+        .rate_limit_budget
+        .exhausted_until(clients.rate_limit_reserve)
+    {
+        let wait = (reset_at - Utc::now()).to_std().unwrap_or_default();
+        if wait > clients.max_rate_limit_wait {
+            return Err(Error::RateLimited {
+                kind: RateLimitKind::Primary,
+                until: reset_at,
+            });
+        }
+
+        info!(
+            reserve = clients.rate_limit_reserve,
+            wait_secs = wait.as_secs(),
+            "GraphQL rate limit reserve reached, sleeping until the window resets"
+        );
+        sleep(wait).await;
+        clients.rate_limit_budget.window_reset();
+    }
+    Ok(())
+}
+
+/// Sleep for a period of time after a failed attempt in [`github_gql_query`].
+async fn gql_sleep(reason: &str, duration: Duration) {
+    warn!(
+        reason,
+        wait_secs = duration.as_secs(),
+        "Request failed, sleeping before retrying"
     );
-    sleep(Duration::from_secs(sleep_secs)).await;
+    sleep(duration).await;
 }
 
 /// Creates the GraphQL mutation to create a new discussion.
@@ -86,28 +429,43 @@ pub async fn create_graphql_request(
     clients: Arc<HttpClients>,
     post: Arc<Post>,
     cat_id: Arc<Id>,
-) -> Operation<CreateCommentsDiscussion, CreateCommentsDiscussionVariables> {
+) -> Result<Operation<CreateCommentsDiscussion, CreateCommentsDiscussionVariables>, Error> {
     use cynic::MutationBuilder;
 
-    let repo_id = get_repo_id(Arc::clone(&clients)).await;
+    let repo_id = get_repo_id(Arc::clone(&clients)).await?;
+
+    Ok(CreateCommentsDiscussion::build(
+        CreateCommentsDiscussionVariables {
+            repo_id,
+            cat_id: cat_id.as_ref().clone(),
+            desc: discussion_body(&post, clients.giscus_strict),
+            title: post.url.path().to_string(),
+        },
+    ))
+}
 
-    // Append a description, if one was found.
+/// Renders the body of the post's discussion: the description, if one was found, followed by a
+/// link to the post and a hidden comment with the post's ID, used to find the discussion if the
+/// post moves. With `strict`, the hash Giscus uses for strict matching is added.
+pub(crate) fn discussion_body(post: &Post, strict: bool) -> String {
     let mut full_desc = post.url.to_string();
     if let Some(mut post_desc) = post.description.clone() {
         post_desc.push_str("\n\n");
         full_desc.insert_str(0, post_desc.as_str());
     }
-
-    CreateCommentsDiscussion::build(CreateCommentsDiscussionVariables {
-        repo_id: repo_id.unwrap(),
-        cat_id: cat_id.as_ref().clone(),
-        desc: full_desc,
-        title: post.url.path().to_string(),
-    })
+    if let Some(id) = &post.id {
+        full_desc.push_str("\n\n");
+        full_desc.push_str(&id_marker(id));
+    }
+    if strict {
+        full_desc.push_str("\n\n");
+        full_desc.push_str(&strict_hash(post.url.path()));
+    }
+    full_desc
 }
 
 /// Retrieves the numeric ID of the repo.
-async fn get_repo_id(clients: Arc<HttpClients>) -> Result<Id, CynicReqwestError> {
+pub(crate) async fn get_repo_id(clients: Arc<HttpClients>) -> Result<Id, Error> {
     let repo_resp: GraphQlResponse<RepoIdQuery> = github_gql_query(
         Arc::clone(&clients),
         RepoIdQueryVariables {
@@ -116,23 +474,25 @@ async fn get_repo_id(clients: Arc<HttpClients>) -> Result<Id, CynicReqwestError>
         },
     )
     .await?;
+    check_errors(&repo_resp)?;
 
-    if let Some(repo_id) = repo_resp
+    repo_resp
         .data
         .and_then(|d| d.repository)
         .map(|repo| repo.id)
-    {
-        Ok(repo_id)
-    } else {
-        panic!(
-            "Repo ID could not be retrieved. GraphQL errors:\n{:#?}",
-            repo_resp.errors.unwrap()
-        );
-    }
+        .ok_or_else(|| {
+            Error::GraphQl(format!(
+                "Repository {}/{} was not found",
+                clients.repo_owner, clients.repo_name
+            ))
+        })
 }
 
-/// Retrieves the numeric ID of the discussion category.
-pub async fn get_category_id(clients: Arc<HttpClients>) -> Result<Id, CynicReqwestError> {
+/// Retrieves the numeric ID of the named discussion category.
+pub(crate) async fn find_category_id(
+    clients: Arc<HttpClients>,
+    category: &str,
+) -> Result<Id, Error> {
     let mut page_end_cursor = None;
     loop {
         let category_resp: GraphQlResponse<CategoryQuery> = github_gql_query(
@@ -144,6 +504,7 @@ pub async fn get_category_id(clients: Arc<HttpClients>) -> Result<Id, CynicReqwe
             },
         )
         .await?;
+        check_errors(&category_resp)?;
 
         if let Some(categories) = category_resp
             .data
@@ -154,7 +515,7 @@ pub async fn get_category_id(clients: Arc<HttpClients>) -> Result<Id, CynicReqwe
                 .edges
                 .iter()
                 .flat_map(|c| &c.node)
-                .find(|cat| cat.name == clients.discussion_category)
+                .find(|cat| cat.name == category)
             {
                 Some(matching_cat) => return Ok(matching_cat.id.clone()),
                 None => {
@@ -163,117 +524,292 @@ pub async fn get_category_id(clients: Arc<HttpClients>) -> Result<Id, CynicReqwe
                         page_end_cursor = categories.page_info.end_cursor;
                         continue;
                     } else {
-                        panic!(
-                            "Category {} was not present in repository {}/{}",
-                            clients.discussion_category, clients.repo_owner, clients.repo_name
-                        );
+                        return Err(Error::CategoryNotFound {
+                            category: category.to_string(),
+                            repo: format!("{}/{}", clients.repo_owner, clients.repo_name),
+                        });
                     }
                 }
             }
         } else {
-            panic!(
-                "No discussion categories found! GraphQL errors:\n{:#?}",
-                category_resp.errors.unwrap()
-            );
+            return Err(Error::GraphQl(format!(
+                "Repository {}/{} was not found",
+                clients.repo_owner, clients.repo_name
+            )));
         }
     }
 }
 
 /// Checks if a discussion with the same title already exists, before creating a new one.
+///
+/// The category is searched first, followed by the other categories of the
+/// [duplicate search](HttpClients::duplicate_search), so a discussion a moderator moved to
+/// another category is still found.
 pub async fn discussion_exists(
     clients: Arc<HttpClients>,
     post: Arc<Post>,
     cat_id: Arc<Id>,
-) -> Result<Option<String>, CynicReqwestError> {
+) -> Result<Option<Discussion>, Error> {
+    for search_id in search_categories(Arc::clone(&clients), Arc::clone(&cat_id)).await? {
+        if let Some(discussion) =
+            find_discussion(Arc::clone(&clients), Arc::clone(&post), &cat_id, search_id).await?
+        {
+            return Ok(Some(discussion));
+        }
+    }
+    Ok(None)
+}
+
+/// The categories searched for the discussion of a post in the category `cat_id`, following the
+/// [duplicate search](HttpClients::duplicate_search). `None` searches every category.
+pub(crate) async fn search_categories(
+    clients: Arc<HttpClients>,
+    cat_id: Arc<Id>,
+) -> Result<Vec<Option<Arc<Id>>>, Error> {
+    Ok(match &clients.duplicate_search {
+        DuplicateSearch::Category => vec![Some(cat_id)],
+        DuplicateSearch::Repository => vec![None],
+        DuplicateSearch::Categories(categories) => {
+            let mut cat_ids = vec![Some(cat_id)];
+            for category in categories {
+                let id = category_id(Arc::clone(&clients), category).await?;
+                if !cat_ids.contains(&Some(Arc::clone(&id))) {
+                    cat_ids.push(Some(id));
+                }
+            }
+            cat_ids
+        }
+    })
+}
+
+/// Searches the category, or every category if `search_id` is `None`, for the discussion titled
+/// with the post's path. Titles only need to be part of the path in the post's own category
+/// `cat_id`, and must match exactly in others.
+async fn find_discussion(
+    clients: Arc<HttpClients>,
+    post: Arc<Post>,
+    cat_id: &Id,
+    search_id: Option<Arc<Id>>,
+) -> Result<Option<Discussion>, Error> {
     let current_time = chrono::Utc::now();
     let max_lookback = chrono::Duration::days(clients.lookback_days);
+    let in_category = |discussion: &Discussion| discussion.category.id == *cat_id;
+
+    let mut existing = None;
+    category_discussions(clients, search_id, |discussion| {
+        // Don't check for discussions older than the lookback period, if enabled
+        if !max_lookback.is_zero() && current_time - created_at(discussion) > max_lookback {
+            return ControlFlow::Break(());
+        } else if in_category(discussion) && post.url.path().contains(&discussion.title) {
+            // Giscus strips the leading stash and file extension from the URL when posting, but still recognizes it.
+            existing = Some(discussion.clone());
+            return ControlFlow::Break(());
+        } else if is_mapping_title(&discussion.title, post.url.path()) {
+            // Other categories are only matched on the exact title, so that a shorter title such
+            // as `/posts` doesn't claim every post below it
+            existing = Some(discussion.clone());
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    })
+    .await?;
+
+    Ok(existing)
+}
 
+/// Pages through the discussions in the category (or every category if `cat_id` is `None`),
+/// newest first, until `visit` breaks or there are no more discussions.
+pub(crate) async fn category_discussions(
+    clients: Arc<HttpClients>,
+    cat_id: Option<Arc<Id>>,
+    mut visit: impl FnMut(&Discussion) -> ControlFlow<()>,
+) -> Result<(), Error> {
     let mut page_end_cursor = None;
     loop {
-        if cfg!(test) {
-            println!("Current after_cursor is {:?}", &page_end_cursor);
-        }
+        debug!(after_cursor = ?page_end_cursor, "Checking page of existing discussions");
 
         let discussion_exists_resp: GraphQlResponse<DiscussionExists> = github_gql_query(
             Arc::clone(&clients),
             DiscussionExistsVariables {
                 owner: &clients.repo_owner,
                 repo_name: &clients.repo_name,
-                cat_id: cat_id.as_ref().clone(),
+                cat_id: cat_id.as_deref().cloned(),
                 after_cursor: page_end_cursor,
             },
         )
         .await?;
+        check_errors(&discussion_exists_resp)?;
 
-        if discussion_exists_resp.errors.is_none() {
-            if let Some(discussions) = discussion_exists_resp
-                .data
-                .and_then(|data| data.repository)
-                .map(|repo| repo.discussions)
-            {
-                for discussion in discussions
-                    .edges
-                    .iter()
-                    .filter_map(|edge| edge.node.as_ref())
-                {
-                    // Don't check for discussions older than the lookback period, if enabled
-                    if !max_lookback.is_zero()
-                        && current_time
-                            - discussion
-                                .created_at
-                                .0
-                                .parse::<chrono::DateTime<chrono::Utc>>()
-                                .unwrap()
-                            > max_lookback
-                    {
-                        return Ok(None);
-                    } else if post.url.path().contains(&discussion.title) {
-                        // Giscus strips the leading stash and file extension from the URL when posting, but still recognizes it.
-                        return Ok(Some(discussion.url.0.clone()));
-                    }
-                }
-
-                // Check if there's another page of results
-                if discussions.page_info.has_next_page {
-                    page_end_cursor = discussions.page_info.end_cursor;
-                    continue;
-                } else {
-                    return Ok(None);
-                }
+        let discussions = match discussion_exists_resp
+            .data
+            .and_then(|data| data.repository)
+            .map(|repo| repo.discussions)
+        {
+            Some(discussions) => discussions,
+            None => {
+                return Err(Error::GraphQl(format!(
+                    "Repository {}/{} was not found",
+                    clients.repo_owner, clients.repo_name
+                )))
+            }
+        };
+        for discussion in discussions
+            .edges
+            .iter()
+            .filter_map(|edge| edge.node.as_ref())
+        {
+            if visit(discussion).is_break() {
+                return Ok(());
             }
         }
 
-        panic!(
-            "Unable to query existing repos. GraphQL errors: \n{:#?}",
-            discussion_exists_resp.errors
-        );
+        // Check if there's another page of results
+        if discussions.page_info.has_next_page {
+            page_end_cursor = discussions.page_info.end_cursor;
+        } else {
+            return Ok(());
+        }
     }
 }
 
+/// Whether one of the last 100 comments on the discussion has the given body, so that a comment
+/// left by an earlier, partly failed run isn't posted again.
+pub(crate) async fn has_comment(
+    clients: Arc<HttpClients>,
+    discussion: &Discussion,
+    body: &str,
+) -> Result<bool, Error> {
+    let resp: GraphQlResponse<DiscussionComments> = github_gql_query(
+        Arc::clone(&clients),
+        DiscussionCommentsVariables {
+            owner: &clients.repo_owner,
+            repo_name: &clients.repo_name,
+            number: discussion.number,
+        },
+    )
+    .await?;
+    check_errors(&resp)?;
+
+    Ok(resp
+        .data
+        .and_then(|d| d.repository)
+        .and_then(|r| r.discussion)
+        .is_some_and(|d| {
+            d.comments
+                .nodes
+                .iter()
+                .any(|comment| comment.body.trim() == body.trim())
+        }))
+}
+
+/// Parses the creation time of the discussion.
+pub(crate) fn created_at(discussion: &Discussion) -> DateTime<Utc> {
+    discussion.created_at.0.parse().unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     //! You must set the `GITHUB_TOKEN` environment variable to run these tests.
     //!
     //! **Note:** these tests operate on the live GitHub API, so be mindful of any potential rate limiting
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
     use std::sync::Arc;
+    use std::thread;
+
+    use std::time::Duration;
 
-    use cynic::Id;
+    use chrono::{TimeZone, Utc};
+    use cynic::{GraphQlResponse, Id};
+    use gh_gql_schema::{DiscussionExists, RepoIdQuery, RepoIdQueryVariables};
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::StatusCode;
     use serial_test::serial;
     use tokio_test::assert_ok;
     use url::Url;
 
-    use crate::gql::{create_graphql_request, discussion_exists, get_category_id, get_repo_id};
-    use crate::{HttpClients, Post};
+    use crate::gql::{
+        create_graphql_request, discussion_body, discussion_exists, find_category_id, get_repo_id,
+        github_gql_query, unsupported_schema, RateLimitHeaders,
+    };
+    use crate::{CommandToken, Error, HttpClients, Post, RateLimitKind};
 
     const BLOG_CATEGORY_ID: &str = "DIC_kwDOJSVgjc4CVgpt";
     const QA_CATEGORY_ID: &str = "DIC_kwDOJSVgjc4CVgpd";
     const TEST_REPO_ID: &str = "R_kgDOJSVgjQ";
 
+    #[test]
+    fn test_rate_limit_headers() {
+        let reset = Utc::now().timestamp() + 120;
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(0));
+        headers.insert("x-ratelimit-reset", HeaderValue::from(reset));
+
+        let rate_limit = RateLimitHeaders::from(&headers);
+        assert_eq!(rate_limit.remaining, Some(0));
+        assert_eq!(rate_limit.reset, Utc.timestamp_opt(reset, 0).single());
+
+        let (kind, wait) = rate_limit
+            .wait_time(StatusCode::OK, r#"{"errors":[{"type":"RATE_LIMITED"}]}"#, 1)
+            .unwrap();
+        assert_eq!(kind, RateLimitKind::Primary);
+        assert!(wait > Duration::from_secs(110) && wait <= Duration::from_secs(121));
+
+        assert_eq!(
+            rate_limit.wait_time(StatusCode::OK, r#"{"data":{}}"#, 1),
+            None
+        );
+    }
+
+    #[test]
+    fn test_secondary_rate_limit() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from(42));
+        assert_eq!(
+            RateLimitHeaders::from(&headers).wait_time(StatusCode::FORBIDDEN, "", 1),
+            Some((RateLimitKind::Secondary, Duration::from_secs(42)))
+        );
+
+        let body = "You have exceeded a secondary rate limit. Please wait a few minutes before you try again.";
+        assert_eq!(
+            RateLimitHeaders::default().wait_time(StatusCode::FORBIDDEN, body, 3),
+            Some((RateLimitKind::Secondary, Duration::from_secs(240)))
+        );
+        assert_eq!(
+            RateLimitHeaders::default().wait_time(
+                StatusCode::FORBIDDEN,
+                "Resource not accessible",
+                1
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_unsupported_schema() {
+        let old_server: GraphQlResponse<DiscussionExists> = serde_json::from_str(
+            r#"{"data":null,"errors":[{"message":"Field 'discussions' doesn't exist on type 'Repository'","locations":[{"line":4,"column":5}]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            unsupported_schema(&old_server),
+            Some("Field 'discussions' doesn't exist on type 'Repository'".to_string())
+        );
+
+        let not_found: GraphQlResponse<DiscussionExists> = serde_json::from_str(
+            r#"{"data":{"repository":null,"rateLimit":null},"errors":[{"message":"Could not resolve to a Repository with the name 'a/b'."}]}"#,
+        )
+        .unwrap();
+        assert_eq!(unsupported_schema(&not_found), None);
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_blogs_category_query() {
         let clients = Arc::new(HttpClients::test_setup(false));
-        let category_id = get_category_id(clients).await;
+        let category_id =
+            find_category_id(Arc::clone(&clients), &clients.discussion_category).await;
 
         assert_ok!(&category_id);
         assert_eq!(category_id.unwrap(), Id::new(BLOG_CATEGORY_ID))
@@ -286,7 +822,8 @@ mod tests {
             discussion_category: "Q&A".to_string(),
             ..HttpClients::test_setup(false)
         });
-        let category_id = get_category_id(clients).await;
+        let category_id =
+            find_category_id(Arc::clone(&clients), &clients.discussion_category).await;
 
         assert_ok!(&category_id);
         assert_eq!(category_id.unwrap(), Id::new(QA_CATEGORY_ID))
@@ -294,14 +831,14 @@ mod tests {
 
     #[tokio::test]
     #[serial]
-    #[should_panic]
     async fn test_missing_category_query() {
         let clients = Arc::new(HttpClients {
             discussion_category: "Removed".to_string(),
             ..HttpClients::test_setup(false)
         });
-        let category_id = get_category_id(clients).await;
-        assert_ok!(&category_id);
+        let category_id =
+            find_category_id(Arc::clone(&clients), &clients.discussion_category).await;
+        assert!(category_id.is_err());
     }
 
     #[tokio::test]
@@ -321,8 +858,12 @@ mod tests {
             ..HttpClients::test_setup(false)
         });
         let post = Arc::new(Post {
+            id: None,
             description: Some("Doesn't matter".to_string()),
             url: Url::parse("https://team-role-org-testing.github.io/jekyll/update/2023/04/03/welcome-to-jekyll.html").unwrap(),
+            tags: Vec::new(),
+            meta: HashMap::new(),
+            giscus: None,
         });
 
         let prev_discussion = discussion_exists(
@@ -332,7 +873,7 @@ mod tests {
         )
         .await;
         assert_ok!(&prev_discussion);
-        assert_eq!(prev_discussion.unwrap(), Some("https://github.com/team-role-org-testing/team-role-org-testing.github.io/discussions/1".to_string()));
+        assert_eq!(prev_discussion.unwrap().map(|d| d.url.0), Some("https://github.com/team-role-org-testing/team-role-org-testing.github.io/discussions/1".to_string()));
     }
 
     #[tokio::test]
@@ -343,8 +884,12 @@ mod tests {
             ..HttpClients::test_setup(false)
         });
         let post = Arc::new(Post {
+            id: None,
             description: None,
             url: Url::parse("https://www.cbc.ca").unwrap(),
+            tags: Vec::new(),
+            meta: HashMap::new(),
+            giscus: None,
         });
 
         let prev_discussion = discussion_exists(
@@ -354,7 +899,7 @@ mod tests {
         )
         .await;
         assert_ok!(&prev_discussion);
-        assert_eq!(prev_discussion.unwrap(), None);
+        assert!(prev_discussion.unwrap().is_none());
     }
 
     /// Testing is done on the orgs/community _(internally, `community/community`)_ repo, a relatively active instance
@@ -371,21 +916,54 @@ mod tests {
             ..HttpClients::test_setup(false)
         });
         let post = Arc::new(Post {
+            id: None,
             description: None,
             url: Url::parse(
                 "irc://a.completely.gibberish.url.that.would.never.be.found/123jf9a92k",
             )
             .unwrap(),
+            tags: Vec::new(),
+            meta: HashMap::new(),
+            giscus: None,
         });
         assert_eq!(
-            get_category_id(Arc::clone(&clients)).await.unwrap(),
+            find_category_id(Arc::clone(&clients), &clients.discussion_category)
+                .await
+                .unwrap(),
             community_general_cat_id
         );
 
         let existing_discussion =
             discussion_exists(clients, post, Arc::new(community_general_cat_id)).await;
         assert_ok!(&existing_discussion);
-        assert_eq!(existing_discussion.unwrap(), None);
+        assert!(existing_discussion.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_discussion_body() {
+        let post = Post {
+            id: Some("https://example.com/?p=42".to_string()),
+            description: Some("A short description".to_string()),
+            url: Url::parse("https://example.com/posts/hello/").unwrap(),
+            tags: Vec::new(),
+            meta: HashMap::new(),
+            giscus: None,
+        };
+        assert_eq!(
+            discussion_body(&post, false),
+            "A short description\n\nhttps://example.com/posts/hello/\n\n<!-- rss_autogen_giscus:id https://example.com/?p=42 -->"
+        );
+        assert_eq!(
+            discussion_body(
+                &Post {
+                    id: None,
+                    description: None,
+                    ..post
+                },
+                true
+            ),
+            "https://example.com/posts/hello/\n\n<!-- sha1: 2ffba58dbd69d5b6f4828c742d3873779769e688 -->"
+        );
     }
 
     #[tokio::test]
@@ -393,14 +971,17 @@ mod tests {
     async fn test_generate_mutation() {
         let clients = Arc::new(HttpClients::test_setup(false));
         let post = Post::get_latest(&clients).await.unwrap();
-        let cat_id = get_category_id(Arc::clone(&clients)).await.unwrap();
+        let cat_id = find_category_id(Arc::clone(&clients), &clients.discussion_category)
+            .await
+            .unwrap();
 
         let mutation = create_graphql_request(
             Arc::clone(&clients),
             Arc::clone(&post),
             Arc::new(cat_id.clone()),
         )
-        .await;
+        .await
+        .unwrap();
 
         assert_eq!(mutation.variables.cat_id, cat_id);
         assert_eq!(mutation.variables.title, post.url.path());
@@ -419,4 +1000,56 @@ mod tests {
             &mutation.variables
         );
     }
+
+    /// A token that is still rejected after being refreshed once fails with `Unauthorized`,
+    /// instead of being refreshed on every attempt.
+    #[tokio::test]
+    async fn test_token_refreshed_once() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut requests = 0;
+            // Further requests would fail to connect, and be retried as a network error
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 401 Unauthorized\r\nContent-Length: 12\r\nConnection: close\r\n\r\nBad password"
+                )
+                .unwrap();
+                requests += 1;
+            }
+            requests
+        });
+
+        let clients = Arc::new(HttpClients {
+            github_gql_url: url,
+            token_provider: Arc::new(CommandToken::new("echo rejected_token")),
+            ..HttpClients::test_setup(true)
+        });
+        let resp: Result<GraphQlResponse<RepoIdQuery>, Error> = github_gql_query(
+            clients,
+            RepoIdQueryVariables {
+                owner: "example",
+                repo_name: "comments",
+            },
+        )
+        .await;
+
+        assert!(matches!(resp, Err(Error::Unauthorized(body)) if body == "Bad password"));
+        assert_eq!(server.join().unwrap(), 2);
+    }
 }
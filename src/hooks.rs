@@ -0,0 +1,368 @@
+use std::env;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use gh_gql_schema::Discussion;
+use ring::hmac;
+use serde::Serialize;
+use tokio::process::Command;
+use tracing::{info, instrument, warn};
+use url::Url;
+
+use crate::{HttpClients, Post};
+
+/// Header holding the HMAC-SHA256 signature of a webhook's body, in the same `sha256=<hex>`
+/// format as GitHub's webhooks.
+pub const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+
+/// Hooks run after a discussion is created, such as announcing it in a team chat or purging a
+/// cache. Failures are reported, but don't fail the run.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Hooks {
+    /// URL that a JSON [payload](HookPayload) is posted to.
+    pub webhook_url: Option<Url>,
+
+    /// Secret used to sign the webhook's body, in the [`SIGNATURE_HEADER`] header.
+    pub webhook_secret: Option<String>,
+
+    /// Shell command run with `sh -c`. The details of the post and discussion are passed in
+    /// `GISCUS_*` environment variables, and the JSON payload in `GISCUS_PAYLOAD`.
+    pub command: Option<String>,
+}
+
+impl Hooks {
+    /// Reads the hooks from the `POST_CREATE_WEBHOOK`, `POST_CREATE_WEBHOOK_SECRET` and
+    /// `POST_CREATE_COMMAND` environment variables.
+    pub(crate) fn from_env() -> Self {
+        Self {
+            webhook_url: env::var("POST_CREATE_WEBHOOK")
+                .ok()
+                .map(|url| url.parse().expect("POST_CREATE_WEBHOOK is not a valid URL")),
+            webhook_secret: env::var("POST_CREATE_WEBHOOK_SECRET").ok(),
+            command: env::var("POST_CREATE_COMMAND").ok(),
+        }
+    }
+}
+
+impl Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("webhook_url", &self.webhook_url)
+            .field(
+                "webhook_secret",
+                &self.webhook_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .field("command", &self.command)
+            .finish()
+    }
+}
+
+/// Details of a created discussion, sent to the hooks.
+#[derive(Debug, Clone, Serialize)]
+pub struct HookPayload {
+    pub event: &'static str,
+    pub post: HookPost,
+    pub discussion: HookDiscussion,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HookPost {
+    pub id: Option<String>,
+    pub url: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HookDiscussion {
+    pub id: String,
+    pub number: i32,
+    pub title: String,
+    pub url: String,
+    pub category: String,
+}
+
+impl HookPayload {
+    pub fn new(post: &Post, discussion: &Discussion) -> Self {
+        Self {
+            event: "discussion_created",
+            post: HookPost {
+                id: post.id.clone(),
+                url: post.url.to_string(),
+                description: post.description.clone(),
+                tags: post.tags.clone(),
+            },
+            discussion: HookDiscussion {
+                id: discussion.id.inner().to_string(),
+                number: discussion.number,
+                title: discussion.title.clone(),
+                url: discussion.url.0.clone(),
+                category: discussion.category.name.clone(),
+            },
+        }
+    }
+
+    /// The environment variables passed to the [command hook](Hooks::command).
+    fn env_vars(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("GISCUS_POST_ID", self.post.id.clone().unwrap_or_default()),
+            ("GISCUS_POST_URL", self.post.url.clone()),
+            (
+                "GISCUS_POST_DESCRIPTION",
+                self.post.description.clone().unwrap_or_default(),
+            ),
+            ("GISCUS_POST_TAGS", self.post.tags.join(",")),
+            ("GISCUS_DISCUSSION_ID", self.discussion.id.clone()),
+            (
+                "GISCUS_DISCUSSION_NUMBER",
+                self.discussion.number.to_string(),
+            ),
+            ("GISCUS_DISCUSSION_TITLE", self.discussion.title.clone()),
+            ("GISCUS_DISCUSSION_URL", self.discussion.url.clone()),
+            (
+                "GISCUS_DISCUSSION_CATEGORY",
+                self.discussion.category.clone(),
+            ),
+        ]
+    }
+}
+
+/// The value of the [`SIGNATURE_HEADER`] for a body signed with the secret.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    format!("sha256={}", to_hex(hmac::sign(&key, body).as_ref()))
+}
+
+/// Formats the bytes as lowercase hexadecimal.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Runs the [configured hooks](HttpClients::hooks) for a newly created discussion.
+///
+/// Returns a message for each hook that failed, which are also logged.
+#[instrument(skip_all, fields(discussion = %discussion.url.0))]
+pub async fn run_hooks(
+    clients: Arc<HttpClients>,
+    post: &Post,
+    discussion: &Discussion,
+) -> Vec<String> {
+    let hooks = &clients.hooks;
+    let payload = HookPayload::new(post, discussion);
+    let mut failures = Vec::new();
+
+    if let Some(url) = &hooks.webhook_url {
+        match send_webhook(&clients, url, &payload).await {
+            Ok(()) => info!(%url, "Sent webhook"),
+            Err(e) => failures.push(format!("Webhook to {url} failed: {e}")),
+        }
+    }
+    if let Some(command) = &hooks.command {
+        match run_command(command, &payload).await {
+            Ok(()) => info!(command, "Ran command hook"),
+            Err(e) => failures.push(format!("Command hook `{command}` failed: {e}")),
+        }
+    }
+
+    for failure in &failures {
+        warn!("{failure}");
+    }
+    failures
+}
+
+async fn send_webhook(
+    clients: &HttpClients,
+    url: &Url,
+    payload: &HookPayload,
+) -> Result<(), reqwest::Error> {
+    let body = serde_json::to_vec(payload).expect("Unable to serialize hook payload");
+    let mut request = clients
+        .html
+        .post(url.clone())
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+    if let Some(secret) = &clients.hooks.webhook_secret {
+        request = request.header(SIGNATURE_HEADER, signature(secret, &body));
+    }
+    request.body(body).send().await?.error_for_status()?;
+    Ok(())
+}
+
+async fn run_command(command: &str, payload: &HookPayload) -> Result<(), String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(payload.env_vars())
+        .env(
+            "GISCUS_PAYLOAD",
+            serde_json::to_string(payload).expect("Unable to serialize hook payload"),
+        )
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(format!(
+            "{}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+
+    use gh_gql_schema::{DateTime, Discussion, DiscussionCategory, Uri};
+    use url::Url;
+
+    use crate::hooks::{run_hooks, signature, Hooks, SIGNATURE_HEADER};
+    use crate::{HttpClients, Post};
+
+    fn post() -> Post {
+        Post {
+            id: Some("https://example.com/?p=42".to_string()),
+            description: Some("Saying hello".to_string()),
+            url: Url::parse("https://example.com/posts/hello/").unwrap(),
+            tags: vec!["rust".to_string()],
+            meta: HashMap::new(),
+            giscus: None,
+        }
+    }
+
+    fn discussion() -> Discussion {
+        Discussion {
+            id: cynic::Id::new("D_kwDOJSVgjc4ATest"),
+            number: 42,
+            title: "/posts/hello/".to_string(),
+            created_at: DateTime("2023-06-10T12:00:00Z".to_string()),
+            url: Uri("https://github.com/example/comments/discussions/42".to_string()),
+            body: String::new(),
+            body_text: String::new(),
+            locked: false,
+            closed: false,
+            category: DiscussionCategory {
+                id: cynic::Id::new("DIC_kwDOJSVgjc4CVgpt"),
+                name: "Blogs".to_string(),
+                is_answerable: false,
+            },
+        }
+    }
+
+    /// Headers and body of a request received by [`listen`].
+    type Request = (Vec<String>, Vec<u8>);
+
+    /// Accepts a single request on a local port, responding with `status`. Returns the URL to
+    /// send it to, and a handle resolving to the request.
+    fn listen(status: &'static str) -> (Url, thread::JoinHandle<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/hook", listener.local_addr().unwrap())).unwrap();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                headers.push(line.trim().to_string());
+            }
+            let length = headers
+                .iter()
+                .find_map(|h| {
+                    h.to_lowercase()
+                        .strip_prefix("content-length: ")?
+                        .parse()
+                        .ok()
+                })
+                .unwrap_or(0);
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+            (headers, body)
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(
+            signature("It's a Secret to Everybody", b"Hello, World!"),
+            "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhook() {
+        let (url, request) = listen("204 No Content");
+        let clients = Arc::new(HttpClients {
+            hooks: Hooks {
+                webhook_url: Some(url),
+                webhook_secret: Some("hook_secret".to_string()),
+                command: None,
+            },
+            ..HttpClients::test_setup(true)
+        });
+
+        assert!(run_hooks(clients, &post(), &discussion()).await.is_empty());
+
+        let (headers, body) = request.join().unwrap();
+        let expected = format!(
+            "{}: {}",
+            SIGNATURE_HEADER.to_lowercase(),
+            signature("hook_secret", &body)
+        );
+        assert!(headers.iter().any(|h| h.to_lowercase() == expected));
+
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["event"], "discussion_created");
+        assert_eq!(json["post"]["url"], "https://example.com/posts/hello/");
+        assert_eq!(json["discussion"]["number"], 42);
+        assert_eq!(json["discussion"]["category"], "Blogs");
+    }
+
+    #[tokio::test]
+    async fn test_failed_hooks() {
+        let (url, request) = listen("500 Internal Server Error");
+        let clients = Arc::new(HttpClients {
+            hooks: Hooks {
+                webhook_url: Some(url),
+                webhook_secret: None,
+                command: Some("echo oops >&2; exit 3".to_string()),
+            },
+            ..HttpClients::test_setup(true)
+        });
+
+        let failures = run_hooks(clients, &post(), &discussion()).await;
+        request.join().unwrap();
+        assert_eq!(failures.len(), 2, "{failures:#?}");
+        assert!(failures[0].starts_with("Webhook to http://127.0.0.1:"));
+        assert!(failures[1].ends_with("exit status: 3: oops"));
+    }
+
+    #[tokio::test]
+    async fn test_command_hook() {
+        let clients = Arc::new(HttpClients {
+            hooks: Hooks {
+                command: Some(
+                    r#"test "$GISCUS_DISCUSSION_NUMBER" = 42 && test "$GISCUS_POST_TAGS" = rust"#
+                        .to_string(),
+                ),
+                ..Hooks::default()
+            },
+            ..HttpClients::test_setup(true)
+        });
+
+        assert!(run_hooks(clients, &post(), &discussion()).await.is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+
+use cynic::{GraphQlResponse, Id};
+use gh_gql_schema::{
+    AddLabelsToDiscussion, AddLabelsToDiscussionVariables, Discussion, LabelsQuery,
+    LabelsQueryVariables,
+};
+use serde::Deserialize;
+use tracing::{info, instrument, warn};
+
+use crate::gql::{check_errors, github_gql_mutation, github_gql_query};
+use crate::rest::github_rest_json;
+use crate::{Error, HttpClients, Post};
+
+/// Colour of labels created by [`create_missing`](LabelConfig::create_missing), matching
+/// GitHub's default grey.
+const NEW_LABEL_COLOR: &str = "ededed";
+
+/// Labels applied to newly created discussions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelConfig {
+    /// Labels applied to every discussion.
+    pub static_labels: Vec<String>,
+
+    /// Maps post [tags](Post::tags) to label names. Tags are matched case-insensitively, so the
+    /// keys are lowercase.
+    pub tag_labels: HashMap<String, String>,
+
+    /// Whether tags without an entry in [`tag_labels`](LabelConfig::tag_labels) are used as
+    /// label names directly.
+    pub tags_as_labels: bool,
+
+    /// Whether labels missing from the repository are created, rather than skipped with a
+    /// warning.
+    pub create_missing: bool,
+}
+
+impl LabelConfig {
+    /// Reads the configuration from the environment variables described in
+    /// [`HttpClients::init`](crate::HttpClients::init). No labels are applied if they are unset.
+    ///
+    /// `LABEL_MAP` is a comma-separated list of `tag=label` pairs. An entry of `*` uses every
+    /// other tag as a label of the same name.
+    pub(crate) fn from_env() -> Self {
+        let mut config = Self {
+            static_labels: env::var("DISCUSSION_LABELS")
+                .map(|labels| split_list(&labels))
+                .unwrap_or_default(),
+            create_missing: env::var("CREATE_MISSING_LABELS")
+                .map(|e| {
+                    e.parse()
+                        .expect("CREATE_MISSING_LABELS must be true or false")
+                })
+                .unwrap_or(false),
+            ..Self::default()
+        };
+
+        for entry in split_list(&env::var("LABEL_MAP").unwrap_or_default()) {
+            if entry == "*" {
+                config.tags_as_labels = true;
+                continue;
+            }
+            let (tag, label) = entry
+                .split_once('=')
+                .unwrap_or_else(|| panic!("Invalid LABEL_MAP entry, expected tag=label: {entry}"));
+            config
+                .tag_labels
+                .insert(tag.trim().to_lowercase(), label.trim().to_string());
+        }
+        config
+    }
+
+    /// The names of the labels for the post, without duplicates.
+    pub fn labels_for(&self, post: &Post) -> Vec<String> {
+        let mapped =
+            post.tags
+                .iter()
+                .filter_map(|tag| match self.tag_labels.get(&tag.to_lowercase()) {
+                    Some(label) => Some(label.clone()),
+                    None if self.tags_as_labels => Some(tag.clone()),
+                    None => None,
+                });
+
+        let mut labels: Vec<String> = Vec::new();
+        for label in self.static_labels.iter().cloned().chain(mapped) {
+            if !labels.iter().any(|l| l.eq_ignore_ascii_case(&label)) {
+                labels.push(label);
+            }
+        }
+        labels
+    }
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Applies the [configured labels](HttpClients::label_config) to a newly created discussion.
+///
+/// Returns the names of any labels that were skipped, because they don't exist in the repository
+/// and [`create_missing`](LabelConfig::create_missing) is disabled.
+#[instrument(skip_all, fields(discussion = %discussion.url.0))]
+pub async fn apply_labels(
+    clients: Arc<HttpClients>,
+    post: &Post,
+    discussion: &Discussion,
+) -> Result<Vec<String>, Error> {
+    let names = clients.label_config.labels_for(post);
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let repo_labels = repo_labels(Arc::clone(&clients)).await?;
+    let mut label_ids = Vec::new();
+    let mut skipped = Vec::new();
+    for name in names {
+        match repo_labels.get(&name.to_lowercase()) {
+            Some(id) => label_ids.push(id.clone()),
+            None if clients.label_config.create_missing => {
+                label_ids.push(create_label(&clients, &name).await?)
+            }
+            None => {
+                warn!(
+                    label = name,
+                    "Label doesn't exist in the repository, skipping"
+                );
+                skipped.push(name);
+            }
+        }
+    }
+    if label_ids.is_empty() {
+        return Ok(skipped);
+    }
+
+    let resp: GraphQlResponse<AddLabelsToDiscussion> = github_gql_mutation(
+        Arc::clone(&clients),
+        AddLabelsToDiscussionVariables {
+            discussion_id: discussion.id.clone(),
+            label_ids,
+        },
+    )
+    .await?;
+    check_errors(&resp)?;
+
+    info!("Applied labels to discussion");
+    Ok(skipped)
+}
+
+/// Retrieves the IDs of the repository's labels, keyed by lowercase name.
+async fn repo_labels(clients: Arc<HttpClients>) -> Result<HashMap<String, Id>, Error> {
+    let mut labels = HashMap::new();
+    let mut page_end_cursor = None;
+    loop {
+        let labels_resp: GraphQlResponse<LabelsQuery> = github_gql_query(
+            Arc::clone(&clients),
+            LabelsQueryVariables {
+                owner: &clients.repo_owner,
+                repo_name: &clients.repo_name,
+                after_cursor: page_end_cursor,
+            },
+        )
+        .await?;
+        check_errors(&labels_resp)?;
+
+        let connection = match labels_resp
+            .data
+            .and_then(|d| d.repository)
+            .and_then(|repo| repo.labels)
+        {
+            Some(connection) => connection,
+            None => return Ok(labels),
+        };
+        for label in connection.edges.into_iter().filter_map(|edge| edge.node) {
+            labels.insert(label.name.to_lowercase(), label.id);
+        }
+
+        // Check if there's another page of results
+        if connection.page_info.has_next_page {
+            page_end_cursor = connection.page_info.end_cursor;
+        } else {
+            return Ok(labels);
+        }
+    }
+}
+
+/// Creates a label with the [REST API](https://docs.github.com/en/rest/issues/labels#create-a-label),
+/// as the GraphQL mutation is only available as a preview. Returns its node ID.
+async fn create_label(clients: &HttpClients, name: &str) -> Result<Id, Error> {
+    #[derive(Deserialize)]
+    struct CreatedLabel {
+        node_id: String,
+    }
+
+    let label: CreatedLabel = github_rest_json(
+        clients
+            .html
+            .post(format!(
+                "{}/repos/{}/{}/labels",
+                clients.github_rest_url, clients.repo_owner, clients.repo_name
+            ))
+            .bearer_auth(clients.token_provider.token(clients).await?)
+            .json(&serde_json::json!({ "name": name, "color": NEW_LABEL_COLOR })),
+    )
+    .await?;
+
+    info!(label = name, "Created missing label");
+    Ok(Id::new(label.node_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::env;
+
+    use serial_test::serial;
+    use url::Url;
+
+    use crate::labels::LabelConfig;
+    use crate::Post;
+
+    fn post(tags: &[&str]) -> Post {
+        Post {
+            id: None,
+            description: None,
+            url: Url::parse("https://example.com/posts/hello/").unwrap(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            meta: HashMap::new(),
+            giscus: None,
+        }
+    }
+
+    #[test]
+    fn test_labels_for() {
+        let config = LabelConfig {
+            static_labels: vec!["blog".to_string()],
+            tag_labels: HashMap::from([("rust".to_string(), "lang: rust".to_string())]),
+            ..LabelConfig::default()
+        };
+        assert_eq!(
+            config.labels_for(&post(&["Rust", "Tutorials"])),
+            ["blog", "lang: rust"]
+        );
+
+        let config = LabelConfig {
+            tags_as_labels: true,
+            ..config
+        };
+        assert_eq!(
+            config.labels_for(&post(&["Rust", "Tutorials", "BLOG"])),
+            ["blog", "lang: rust", "Tutorials"]
+        );
+        assert!(LabelConfig::default()
+            .labels_for(&post(&["Rust"]))
+            .is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_from_env() {
+        env::set_var("DISCUSSION_LABELS", "blog, comments,");
+        env::set_var("LABEL_MAP", "Rust=lang: rust, *");
+        env::set_var("CREATE_MISSING_LABELS", "true");
+        let config = LabelConfig::from_env();
+        env::remove_var("DISCUSSION_LABELS");
+        env::remove_var("LABEL_MAP");
+        env::remove_var("CREATE_MISSING_LABELS");
+
+        assert_eq!(
+            config,
+            LabelConfig {
+                static_labels: vec!["blog".to_string(), "comments".to_string()],
+                tag_labels: HashMap::from([("rust".to_string(), "lang: rust".to_string())]),
+                tags_as_labels: true,
+                create_missing: true,
+            }
+        );
+        assert_eq!(LabelConfig::from_env(), LabelConfig::default());
+    }
+}
@@ -18,26 +18,79 @@
 //! Generally the program will panic to exit. There is one exception to this rule - if a discussion
 //! post already exists, it will use [`COMMENTS_EXIST`].
 
+mod actions;
+mod auth;
+mod budget;
+mod embed;
+mod error;
+#[cfg(test)]
+mod fixtures;
 mod gql;
+mod hooks;
+mod labels;
+mod lock;
+mod logging;
+mod moved;
 mod post;
+mod preflight;
+mod reconcile;
+mod report;
+mod rest;
+mod retry;
+mod routing;
+mod scrape;
+mod seed;
+mod source;
+mod transport;
+mod update;
 
 use std::env;
+use std::fs;
 use std::process;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
-use cynic::http::{CynicReqwestError, ReqwestExt};
+use gh_gql_schema::Discussion;
 use reqwest::header::USER_AGENT;
 use reqwest::{
-    header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION},
+    header::{HeaderMap, HeaderValue, ACCEPT},
     Client,
 };
 use tokio::join;
+use tracing::{info, instrument, warn};
 
+pub use actions::GitHubActions;
+pub use auth::{CommandToken, FileToken, GitHubApp, StaticToken, TokenProvider};
+pub use budget::RateLimitBudget;
+pub use embed::{
+    check_embed, giscus_snippet, EmbedCheck, GiscusEmbed, GiscusScript, SnippetFormat,
+    SnippetOptions, MAPPING,
+};
+pub use error::{Error, RateLimitKind};
+pub use hooks::{run_hooks, HookPayload, Hooks, SIGNATURE_HEADER};
+pub use labels::{apply_labels, LabelConfig};
+pub use lock::{lock_stale, LockOptions};
+pub use logging::init_logging;
+pub use moved::MovedPosts;
 pub use post::Post;
+pub use preflight::preflight;
+pub use reconcile::{reconcile, ReconcileMode, ReconcileOptions};
+pub use report::{process_post, Action, PostReport, RunReport};
+pub use rest::{check_server, ServerMeta, MIN_GHES_VERSION};
+pub use retry::RetryPolicy;
+pub use routing::{CategoryRoute, CategoryRoutes, DuplicateSearch};
+pub use scrape::{BasicAuth, ScrapeConfig, SiteOptions, DEFAULT_USER_AGENT};
+pub use seed::{seed_discussion, SeedComment};
+pub use source::{find_posts, ChangeRange, ChangedPosts, PostSource};
+pub use transport::TransportConfig;
+pub use update::{find_posts_to_update, update_post, KEEP_END, KEEP_START};
 
-use gql::{create_graphql_request, discussion_exists, get_category_id};
+use gql::{
+    check_errors, create_graphql_request, discussion_exists, run_create_discussion, CreateResult,
+};
+use moved::rename_moved_discussion;
+use routing::post_category_id;
 
 /// Exit code for when comments already exist (as
 /// in [HTTP 303 See Other](https://www.rfc-editor.org/rfc/rfc9110.html#section-15.4.4))
@@ -54,14 +107,19 @@ pub struct HttpClients {
     /// following headers, using [`ClientBuilder::default_headers`](reqwest::ClientBuilder::default_headers):
     ///
     /// - `Accept: application/vnd.github+json`
-    /// - `Authorization: <GitHub token>`
     /// - `User-Agent: <appropriate user agent name>`
     /// - `X-Github-Next-Global-ID: 1`
+    ///
+    /// The `Authorization` header is added to each request by the
+    /// [token provider](HttpClients::token_provider).
     pub gql: Client,
 
     /// URL for the blog's RSS feed.
     pub website_rss_url: String,
 
+    /// URL for the GitHub server, typically <https://github.com>
+    pub github_server_url: String,
+
     /// URL for GitHub REST API, typically <https://api.github.com>
     pub github_rest_url: String,
 
@@ -74,12 +132,63 @@ pub struct HttpClients {
     /// Name of the repository hosting the comments
     pub repo_name: String,
 
-    /// Name of the discussion category that the comments should be posted under.
+    /// Name of the discussion category that the comments should be posted under, unless a post
+    /// is routed to another category by [`category_routes`](HttpClients::category_routes).
     pub discussion_category: String,
 
+    /// Rules routing posts to discussion categories other than
+    /// [`discussion_category`](HttpClients::discussion_category).
+    pub category_routes: CategoryRoutes,
+
+    /// The categories searched for an existing discussion of a post, before creating one.
+    pub duplicate_search: DuplicateSearch,
+
+    /// Whether Giscus uses strict title matching. If so, the hash Giscus looks for is added to the
+    /// body of each discussion, and the [embed snippet](giscus_snippet) sets `data-strict="1"`.
+    pub giscus_strict: bool,
+
+    /// What is done when a post's page embeds Giscus with other settings than configured.
+    pub embed_check: EmbedCheck,
+
     /// The number of days to look back in history, to check if a previous discussion occurred.
     /// Limit is disabled if set to 0.
     pub lookback_days: i64,
+
+    /// The longest period to wait for a GitHub rate limit to reset. If GitHub requires a longer
+    /// wait, [`Error::RateLimited`] is returned instead.
+    pub max_rate_limit_wait: Duration,
+
+    /// GraphQL points to leave unused, for other workflows sharing the same token. Once the
+    /// remaining budget reaches this amount, queries wait for the rate limit window to reset.
+    pub rate_limit_reserve: u64,
+
+    /// GraphQL points consumed and remaining, shared between clones of this struct.
+    pub rate_limit_budget: Arc<RateLimitBudget>,
+
+    /// How failed requests to GitHub are retried, for both queries and the mutation creating the
+    /// discussion.
+    pub retry_policy: RetryPolicy,
+
+    /// Supplies the token used to authenticate requests to GitHub.
+    pub token_provider: Arc<dyn TokenProvider>,
+
+    /// User agent, per-site options and request pacing for fetching the feed and post pages.
+    pub scrape_config: ScrapeConfig,
+
+    /// Where the posts to create discussions for are found.
+    pub post_source: PostSource,
+
+    /// Labels applied to newly created discussions.
+    pub label_config: LabelConfig,
+
+    /// How the discussions of posts whose URL changed are found and retitled.
+    pub moved_posts: MovedPosts,
+
+    /// The first comment posted on newly created discussions.
+    pub seed_comment: SeedComment,
+
+    /// Hooks run after a discussion is created.
+    pub hooks: Hooks,
 }
 
 impl HttpClients {
@@ -88,14 +197,60 @@ impl HttpClients {
     /// [default values available in GitHub Actions](https://docs.github.com/en/actions/learn-github-actions/variables#default-environment-variables),
     /// except for `DISCUSSION_CATEGORY` and `LOOKBACK_DAYS`:
     ///
-    /// - `GITHUB_TOKEN`, used in the authorization header for the [GraphQL client](HttpClients::gql)
+    /// - `GITHUB_TOKEN`, used in the authorization header for the [GraphQL client](HttpClients::gql),
+    ///   required unless one of the other [token providers](HttpClients::token_provider) is used:
+    ///   - `GITHUB_APP_ID`, to authenticate as a [`GitHubApp`]. Also requires the app's private key,
+    ///     either as the PEM contents in `GITHUB_APP_PRIVATE_KEY` or a path in
+    ///     `GITHUB_APP_PRIVATE_KEY_PATH`. `GITHUB_APP_INSTALLATION_ID` is optional.
+    ///   - `GITHUB_TOKEN_COMMAND`, a [credential helper command](CommandToken) printing the token
+    ///   - `GITHUB_TOKEN_FILE`, the path of a [file containing the token](FileToken)
     /// - [`WEBSITE_RSS_URL`](HttpClients::website_rss_url), required
-    /// - [`GITHUB_API_URL`](HttpClients::github_rest_url), optional (defaults to <https://api.github.com>)
-    /// - [`GITHUB_GRAPHQL_URL`](HttpClients::github_gql_url), optional (defaults to <https://api.github.com/graphql>)
+    /// - [`GITHUB_SERVER_URL`](HttpClients::github_server_url), optional (defaults to <https://github.com>).
+    ///   For GitHub Enterprise Server, the API URLs are derived from this as `<server>/api/v3` and
+    ///   `<server>/api/graphql`.
+    /// - [`GITHUB_API_URL`](HttpClients::github_rest_url), optional (overrides the URL derived from `GITHUB_SERVER_URL`)
+    /// - [`GITHUB_GRAPHQL_URL`](HttpClients::github_gql_url), optional (overrides the URL derived from `GITHUB_SERVER_URL`)
     /// - [`GITHUB_REPOSITORY_OWNER`](HttpClients::repo_owner), required
     /// - `GITHUB_REPOSITORY` in format `<owner>/<repo>`, required (mapped to [`repo_name`](HttpClients::repo_name))
     /// - [`DISCUSSION_CATEGORY`](HttpClients::discussion_category) as the name of the category to post under, required
+    /// - [`CATEGORY_ROUTES`](HttpClients::category_routes), a JSON array of rules routing posts to
+    ///   other categories, optional (see [`CategoryRoutes::from_env`])
+    /// - [`DUPLICATE_SEARCH_CATEGORIES`](HttpClients::duplicate_search), `*` to search every
+    ///   category for an existing discussion, or comma-separated category names to also search,
+    ///   optional (defaults to only the post's category)
+    /// - [`GISCUS_STRICT`](HttpClients::giscus_strict), optional (defaults to `false`)
+    /// - [`GISCUS_CHECK`](HttpClients::embed_check) as `off`, `warn` or `refuse`, optional
+    ///   (defaults to `warn`)
     /// - [`LOOKBACK_DAYS`](HttpClients::lookback_days), optional (defaults to 7)
+    /// - [`MAX_RATE_LIMIT_WAIT`](HttpClients::max_rate_limit_wait) in seconds, optional (defaults to 900)
+    /// - [`RATE_LIMIT_RESERVE`](HttpClients::rate_limit_reserve), optional (defaults to 0)
+    /// - `RETRY_MAX_ATTEMPTS`, `RETRY_BASE_DELAY` and `RETRY_MAX_DELAY` (in seconds), `RETRY_JITTER`
+    ///   and `RETRY_STATUSES` (comma-separated), optional (see [`RetryPolicy`] for defaults)
+    /// - `PROXY_URL`, `NO_PROXY`, `EXTRA_CA_CERTS` (path of a PEM bundle), `CLIENT_CERT_PATH` and
+    ///   `CLIENT_KEY_PATH`, and `CONNECT_TIMEOUT` and `REQUEST_TIMEOUT` (in seconds), optional
+    ///   (see [`TransportConfig`] for defaults)
+    /// - `HTML_USER_AGENT`, `REQUEST_INTERVAL` (in seconds, per host) and `SITE_CONFIG` (JSON),
+    ///   optional (see [`ScrapeConfig`] for defaults)
+    /// - [`POST_SOURCE`](HttpClients::post_source), optional (defaults to `feed`). Set to `push` to
+    ///   use the posts added by the triggering push event, or a git range such as `A..B`. These
+    ///   also use (see [`ChangedPosts`]):
+    ///   - `POST_PATHS`, comma-separated globs of post content files (defaults to `_posts/**/*` and
+    ///     `content/posts/**/*.md`)
+    ///   - `PERMALINK_PATTERN` (defaults to `/:categories/:year/:month/:day/:title.html`)
+    ///   - `SITE_URL` (defaults to the origin of `WEBSITE_RSS_URL`)
+    /// - [`DISCUSSION_LABELS`](HttpClients::label_config), comma-separated labels for every new
+    ///   discussion, and `LABEL_MAP`, comma-separated `tag=label` pairs (or `*` to use the post's
+    ///   tags as labels), optional. Set `CREATE_MISSING_LABELS` to `true` to create labels missing
+    ///   from the repository (see [`LabelConfig`]).
+    /// - [`DETECT_MOVED_POSTS`](HttpClients::moved_posts), optional (defaults to `false`), and
+    ///   `MOVED_POST_COMMENT`, a comment posted when a moved post's discussion is retitled,
+    ///   optional (see [`MovedPosts`])
+    /// - [`SEED_COMMENT`](HttpClients::seed_comment), a template for the first comment on new
+    ///   discussions (or `SEED_COMMENT_FILE`, its path), `SEED_COMMENT_AS_ANSWER` and
+    ///   `SEED_RELATED_LIMIT`, optional (see [`SeedComment`])
+    /// - [`POST_CREATE_WEBHOOK`](HttpClients::hooks), a URL sent the details of each new
+    ///   discussion, signed with `POST_CREATE_WEBHOOK_SECRET`, and `POST_CREATE_COMMAND`, a shell
+    ///   command run for each new discussion, optional (see [`Hooks`])
     ///
     /// ```rust
     /// use std::env;
@@ -111,18 +266,52 @@ impl HttpClients {
     /// let clients = HttpClients::init();
     /// ```
     pub fn init() -> Arc<Self> {
-        let (html_client, gql_client) = Self::clients(false);
+        let website_rss_url =
+            env::var("WEBSITE_RSS_URL").expect("WEBSITE_RSS_URL env var is required");
 
         Arc::new(Self {
+            post_source: PostSource::from_env(&website_rss_url),
+            website_rss_url,
+            category_routes: CategoryRoutes::from_env(),
+            duplicate_search: DuplicateSearch::from_env(),
+            embed_check: env::var("GISCUS_CHECK")
+                .map(|e| e.parse().unwrap())
+                .unwrap_or_default(),
+            lookback_days: env::var("LOOKBACK_DAYS")
+                .map_or(7, |e| i64::from_str(e.as_str()).unwrap()),
+            label_config: LabelConfig::from_env(),
+            moved_posts: MovedPosts::from_env(),
+            seed_comment: SeedComment::from_env(),
+            hooks: Hooks::from_env(),
+            ..Self::github_from_env(None)
+        })
+    }
+
+    /// Create the clients for GitHub alone, such as for the [embed snippet](giscus_snippet). Only
+    /// the GitHub, transport and rate limit variables of [`init`](HttpClients::init) are read, and
+    /// `DISCUSSION_CATEGORY` isn't required if `category` is given. Other settings keep their
+    /// defaults, without a [feed URL](HttpClients::website_rss_url).
+    pub fn init_github(category: Option<String>) -> Arc<Self> {
+        Arc::new(Self::github_from_env(category))
+    }
+
+    fn github_from_env(category: Option<String>) -> Self {
+        let scrape_config = ScrapeConfig::from_env();
+        let (html_client, gql_client) = Self::clients(&scrape_config.user_agent);
+        let github_server_url = env::var("GITHUB_SERVER_URL")
+            .map_or("https://github.com".to_string(), |e| {
+                e.trim_end_matches('/').to_string()
+            });
+        let (github_rest_url, github_gql_url) = rest::api_urls(&github_server_url);
+
+        Self {
             html: html_client,
             gql: gql_client,
-            website_rss_url: env::var("WEBSITE_RSS_URL")
-                .expect("WEBSITE_RSS_URL env var is required"),
+            website_rss_url: String::new(),
 
-            github_rest_url: env::var("GITHUB_API_URl")
-                .unwrap_or("https://api.github.com".to_string()),
-            github_gql_url: env::var("GITHUB_GRAPHQL_URL")
-                .unwrap_or("https://api.github.com/graphql".to_string()),
+            github_rest_url: env::var("GITHUB_API_URL").unwrap_or(github_rest_url),
+            github_gql_url: env::var("GITHUB_GRAPHQL_URL").unwrap_or(github_gql_url),
+            github_server_url,
             repo_owner: env::var("GITHUB_REPOSITORY_OWNER")
                 .expect("Repo owner was not found (GITHUB_REPOSITORY_OWNER)"),
             repo_name: env::var("GITHUB_REPOSITORY")
@@ -131,28 +320,75 @@ impl HttpClients {
                 .expect("Not a valid repo/name string")
                 .1
                 .into(),
-            discussion_category: env::var("DISCUSSION_CATEGORY")
-                .expect("DISCUSSION_CATEGORY env var is required"),
-            lookback_days: env::var("LOOKBACK_DAYS")
-                .map_or(7, |e| i64::from_str(e.as_str()).unwrap()),
-        })
+            discussion_category: category.unwrap_or_else(|| {
+                env::var("DISCUSSION_CATEGORY").expect("DISCUSSION_CATEGORY env var is required")
+            }),
+            category_routes: CategoryRoutes::default(),
+            duplicate_search: DuplicateSearch::default(),
+            giscus_strict: env::var("GISCUS_STRICT")
+                .map(|e| e.parse().expect("GISCUS_STRICT must be true or false"))
+                .unwrap_or(false),
+            embed_check: EmbedCheck::default(),
+            lookback_days: 7,
+            max_rate_limit_wait: env::var("MAX_RATE_LIMIT_WAIT")
+                .map_or(Duration::from_secs(900), |e| {
+                    Duration::from_secs(u64::from_str(e.as_str()).unwrap())
+                }),
+            rate_limit_reserve: env::var("RATE_LIMIT_RESERVE")
+                .map_or(0, |e| u64::from_str(e.as_str()).unwrap()),
+            rate_limit_budget: Arc::default(),
+            retry_policy: RetryPolicy::from_env(),
+            token_provider: Self::token_provider(),
+            scrape_config,
+            post_source: PostSource::Feed,
+            label_config: LabelConfig::default(),
+            moved_posts: MovedPosts::default(),
+            seed_comment: SeedComment::default(),
+            hooks: Hooks::default(),
+        }
     }
 
-    /// A small function to create the HTML and GraphQL clients, mainly for testing purposes.
-    ///
-    /// Passing `true` will replace `GITHUB_TOKEN` with a fake value, so that the environment
-    /// variable does not need to be set.
-    fn clients(use_placeholder_github_token: bool) -> (Client, Client) {
-        let token = match use_placeholder_github_token {
-            true => String::from("00112233FAKE_TOKEN44556677"),
-            false => env::var("GITHUB_TOKEN").expect("GITHUB_TOKEN env var is required"),
+    /// Selects the token provider from the environment, in order of preference: GitHub App,
+    /// credential helper command, token file, then `GITHUB_TOKEN`.
+    fn token_provider() -> Arc<dyn TokenProvider> {
+        if let Some(app) = Self::github_app() {
+            return Arc::new(app);
+        }
+        if let Ok(command) = env::var("GITHUB_TOKEN_COMMAND") {
+            return Arc::new(CommandToken::new(command));
+        }
+        if let Ok(path) = env::var("GITHUB_TOKEN_FILE") {
+            return Arc::new(FileToken::new(path));
+        }
+
+        Arc::new(StaticToken(
+            env::var("GITHUB_TOKEN").expect("GITHUB_TOKEN env var is required"),
+        ))
+    }
+
+    /// Reads the GitHub App credentials from the environment, if `GITHUB_APP_ID` is set.
+    fn github_app() -> Option<GitHubApp> {
+        let app_id = env::var("GITHUB_APP_ID").ok()?;
+        let private_key = match env::var("GITHUB_APP_PRIVATE_KEY") {
+            Ok(pem) => pem.into_bytes(),
+            Err(_) => fs::read(env::var("GITHUB_APP_PRIVATE_KEY_PATH").expect(
+                "GITHUB_APP_PRIVATE_KEY or GITHUB_APP_PRIVATE_KEY_PATH env var is required with GITHUB_APP_ID",
+            ))
+            .expect("Unable to read GitHub App private key"),
         };
+        let installation_id = env::var("GITHUB_APP_INSTALLATION_ID")
+            .ok()
+            .map(|e| u64::from_str(e.as_str()).unwrap());
 
+        Some(
+            GitHubApp::new(app_id, &private_key, installation_id)
+                .expect("Invalid GitHub App private key"),
+        )
+    }
+
+    /// A small function to create the HTML and GraphQL clients, mainly for testing purposes.
+    fn clients(user_agent: &str) -> (Client, Client) {
         let mut gh_headers = HeaderMap::new();
-        gh_headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(format!("Bearer {token}").as_str()).unwrap(),
-        );
         gh_headers.insert(
             ACCEPT,
             HeaderValue::from_static("application/vnd.github+json"),
@@ -166,18 +402,19 @@ impl HttpClients {
             HeaderValue::from_str("rss_autogen_giscus").unwrap(),
         );
 
+        let transport = TransportConfig::from_env();
         (
-            Client::builder()
-                .user_agent("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) rss-autogen-giscus/0.1.0 Chrome/113.0.0.0 Safari/537.36")
-                .timeout(Duration::from_secs(60))
+            transport
+                .client_builder()
+                .user_agent(user_agent)
                 .build()
                 .expect("Unable to build REST client"),
-            Client::builder()
-                .timeout(Duration::from_secs(60))
+            transport
+                .client_builder()
                 .default_headers(gh_headers)
                 .build()
-                .expect("Unable to build GraphQL client")
-            )
+                .expect("Unable to build GraphQL client"),
+        )
     }
 
     /// Creates an instance for testing purposes.
@@ -185,22 +422,47 @@ impl HttpClients {
     /// If the GITHUB_TOKEN does not need to be set, a placeholder value can be used.
     #[cfg(test)]
     fn test_setup(use_placeholder_github_token: bool) -> Self {
-        let (html, gql) = Self::clients(use_placeholder_github_token);
+        let token = match use_placeholder_github_token {
+            true => String::from("00112233FAKE_TOKEN44556677"),
+            false => env::var("GITHUB_TOKEN").expect("GITHUB_TOKEN env var is required"),
+        };
+        let scrape_config = ScrapeConfig::default();
+        let (html, gql) = Self::clients(&scrape_config.user_agent);
         Self {
             html,
             gql,
             website_rss_url: "https://team-role-org-testing.github.io/feed.xml".to_string(),
+            github_server_url: "https://github.com".to_string(),
             github_rest_url: "https://api.github.com".to_string(),
             github_gql_url: "https://api.github.com/graphql".to_string(),
             repo_owner: "team-role-org-testing".to_string(),
             repo_name: "team-role-org-testing.github.io".to_string(),
             discussion_category: "Blogs".to_string(),
+            category_routes: CategoryRoutes::default(),
+            duplicate_search: DuplicateSearch::default(),
+            giscus_strict: false,
+            embed_check: EmbedCheck::default(),
             lookback_days: 7,
+            max_rate_limit_wait: Duration::from_secs(900),
+            rate_limit_reserve: 0,
+            rate_limit_budget: Arc::default(),
+            retry_policy: RetryPolicy::default(),
+            token_provider: Arc::new(StaticToken(token)),
+            scrape_config,
+            post_source: PostSource::Feed,
+            label_config: LabelConfig::default(),
+            moved_posts: MovedPosts::default(),
+            seed_comment: SeedComment::default(),
+            hooks: Hooks::default(),
         }
     }
 }
 
-/// Create the GitHub Discussion post for Giscus.
+/// Create the GitHub Discussion post for Giscus, by [processing the post](process_post) and
+/// logging its warnings. A failure is returned as [`Error::PostFailed`].
+///
+/// If a new discussion was created, the process exits with [`COMMENTS_EXIST`]. Use
+/// [`process_post`] or [`find_or_create_discussion`] to handle the result instead.
 ///
 /// # Example
 ///
@@ -208,61 +470,112 @@ impl HttpClients {
 /// the [required environment variables](HttpClients::init).
 ///
 /// ```rust
-/// use cynic::http::CynicReqwestError;
-/// use rss_autogen_giscus::{create_discussion, HttpClients, Post};
+/// use rss_autogen_giscus::{create_discussion, Error, HttpClients, Post};
 ///
 /// #[tokio::main]
-/// pub async fn main() -> Result<(), CynicReqwestError> {
+/// pub async fn main() -> Result<(), Error> {
 ///     let clients = HttpClients::init();
 ///     let latest_post = Post::get_latest(&clients).await?;
 ///
 ///     create_discussion(clients, latest_post).await
 /// }
 /// ```
-pub async fn create_discussion(
+pub async fn create_discussion(clients: Arc<HttpClients>, post: Arc<Post>) -> Result<(), Error> {
+    let report = process_post(Arc::clone(&clients), post).await;
+    for warning in &report.warnings {
+        warn!(post = report.url, "{warning}");
+    }
+    report_rate_limit_usage(&clients);
+
+    match report.action {
+        Action::Failed => Err(Error::PostFailed {
+            url: report.url,
+            errors: report.errors,
+        }),
+        Action::Created => process::exit(COMMENTS_EXIST),
+        _ => Ok(()),
+    }
+}
+
+/// Finds the existing discussion for the post, or creates a new one.
+///
+/// Returns [`Action::Created`] or [`Action::Exists`], along with the discussion. If the post's URL
+/// changed, its discussion is retitled instead, returning [`Action::Renamed`].
+#[instrument(skip_all, fields(post = %post.url))]
+pub async fn find_or_create_discussion(
     clients: Arc<HttpClients>,
     post: Arc<Post>,
-) -> Result<(), CynicReqwestError> {
-    let cat_id = Arc::new(get_category_id(Arc::clone(&clients)).await?);
+) -> Result<(Action, Discussion), Error> {
+    let cat_id = post_category_id(Arc::clone(&clients), &post).await?;
 
     let (is_existing_discussion, create_disc_op) = join!(
         discussion_exists(Arc::clone(&clients), Arc::clone(&post), Arc::clone(&cat_id)),
         create_graphql_request(Arc::clone(&clients), Arc::clone(&post), Arc::clone(&cat_id))
     );
 
-    if is_existing_discussion.as_ref().unwrap().is_some() {
-        println!(
-            "Discussion was not created for {}\n--> An existing discussion was found at {}",
-            &post.url,
-            is_existing_discussion?.unwrap()
+    if let Some(discussion) = is_existing_discussion? {
+        info!(
+            discussion_url = discussion.url.0,
+            "Discussion was not created, an existing discussion was found"
         );
-        return Ok(());
+        return Ok((Action::Exists, discussion));
     }
 
-    let create_disc_resp = clients
-        .gql
-        .post(&clients.github_gql_url)
-        .run_graphql(create_disc_op)
-        .await?;
+    // Only needed once no existing discussion was found
+    let create_disc_op = create_disc_op?;
+    if clients.moved_posts.enabled {
+        if let Some(discussion) =
+            rename_moved_discussion(Arc::clone(&clients), Arc::clone(&post), Arc::clone(&cat_id))
+                .await?
+        {
+            return Ok((Action::Renamed, discussion));
+        }
+    }
 
-    if let Some(discussion_info) = create_disc_resp
+    let create_disc_resp = match run_create_discussion(
+        Arc::clone(&clients),
+        Arc::clone(&post),
+        Arc::clone(&cat_id),
+        create_disc_op,
+    )
+    .await?
+    {
+        CreateResult::Created(resp) => resp,
+        CreateResult::Existing(discussion) => {
+            info!(
+                discussion_url = discussion.url.0,
+                "Discussion was not created, a previous attempt created the discussion"
+            );
+            return Ok((Action::Exists, discussion));
+        }
+    };
+
+    check_errors(&create_disc_resp)?;
+    match create_disc_resp
         .data
         .and_then(|d| d.create_discussion)
         .and_then(|payload| payload.discussion)
     {
-        if discussion_info.title == post.url.path() {
-            println!(
-                "Successfully created new discussion at {} ({})",
-                String::from(discussion_info.url),
-                discussion_info.title
+        Some(discussion) => {
+            info!(
+                discussion_url = discussion.url.0,
+                title = discussion.title,
+                "Successfully created new discussion"
             );
-            process::exit(COMMENTS_EXIST);
+            Ok((Action::Created, discussion))
         }
-    } else {
-        panic!(
-            "Discussion could not be generated. GraphQL errors: \n{:#?}",
-            create_disc_resp.errors
-        );
+        None => Err(Error::GraphQl(
+            "GitHub didn't return the created discussion".to_string(),
+        )),
     }
-    Ok(())
+}
+
+/// Logs the GraphQL points consumed during the run.
+fn report_rate_limit_usage(clients: &HttpClients) {
+    let budget = &clients.rate_limit_budget;
+    info!(
+        consumed = budget.consumed(),
+        remaining = budget.remaining(),
+        "GraphQL rate limit usage"
+    );
 }
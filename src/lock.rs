@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use cynic::GraphQlResponse;
+use gh_gql_schema::{
+    AddComment, AddCommentVariables, Discussion, LockDiscussion, LockDiscussionVariables,
+};
+use tracing::{info, instrument};
+use url::Url;
+
+use crate::embed::is_mapping_title;
+use crate::gql::{
+    category_discussions, check_errors, created_at, github_gql_mutation, github_gql_mutation_once,
+    has_comment,
+};
+use crate::post::{discussion_post_url, feed_entries};
+use crate::routing::category_id;
+use crate::{Action, Error, HttpClients, PostReport};
+
+/// Options for [`lock_stale`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockOptions {
+    /// Discussions are locked once they were created, or their post was published, more than
+    /// this many days ago.
+    pub days: i64,
+
+    /// Comment posted to each discussion before it is locked, such as "Comments are closed".
+    pub comment: Option<String>,
+
+    /// URL paths of evergreen posts, whose discussions are never locked.
+    pub evergreen: Vec<String>,
+
+    /// Report the discussions that would be locked, without changing them.
+    pub dry_run: bool,
+}
+
+impl Default for LockOptions {
+    fn default() -> Self {
+        Self {
+            days: 90,
+            comment: None,
+            evergreen: Vec::new(),
+            dry_run: false,
+        }
+    }
+}
+
+impl LockOptions {
+    /// Whether the discussion's comment window has closed at `now`. `published` holds the
+    /// publication dates of the posts in the feed, keyed by URL path.
+    fn is_stale(
+        &self,
+        discussion: &Discussion,
+        published: &HashMap<String, DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let matches_post = |path: &String| is_mapping_title(&discussion.title, path);
+        if discussion.locked || self.evergreen.iter().any(matches_post) {
+            return false;
+        }
+
+        let max_age = Duration::days(self.days);
+        now - created_at(discussion) > max_age
+            || published
+                .iter()
+                .any(|(path, date)| matches_post(path) && now - *date > max_age)
+    }
+}
+
+/// Locks the discussions in the categories whose comment window has closed, optionally posting a
+/// closing comment first.
+///
+/// Returns a report for each discussion that was (or, in a [dry run](LockOptions::dry_run), would
+/// be) locked.
+#[instrument(skip_all, fields(days = options.days, dry_run = options.dry_run))]
+pub async fn lock_stale(
+    clients: Arc<HttpClients>,
+    options: &LockOptions,
+) -> Result<Vec<PostReport>, Error> {
+    let published: HashMap<String, DateTime<Utc>> = feed_entries(&clients)
+        .await?
+        .into_iter()
+        .filter_map(|entry| {
+            let url: Url = entry.links.first()?.href.parse().ok()?;
+            Some((url.path().to_string(), entry.published.or(entry.updated)?))
+        })
+        .collect();
+
+    let now = Utc::now();
+    let mut stale = Vec::new();
+    for category in clients
+        .category_routes
+        .categories(&clients.discussion_category)
+    {
+        let cat_id = category_id(Arc::clone(&clients), category).await?;
+        category_discussions(Arc::clone(&clients), Some(cat_id), |discussion| {
+            if options.is_stale(discussion, &published, now) {
+                stale.push(discussion.clone());
+            }
+            ControlFlow::Continue(())
+        })
+        .await?;
+    }
+
+    let mut reports = Vec::new();
+    for discussion in stale {
+        let post_url = discussion_post_url(&clients, &discussion);
+        let mut report = PostReport::for_discussion(&post_url, &discussion, Action::Locked);
+
+        if options.dry_run {
+            info!(discussion_url = discussion.url.0, "Would lock discussion");
+        } else if let Err(e) = lock(Arc::clone(&clients), &discussion, options).await {
+            report.action = Action::Failed;
+            report.errors.push(e.to_string());
+        } else {
+            info!(discussion_url = discussion.url.0, "Locked discussion");
+        }
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+/// Posts the closing comment, if any, then locks the discussion.
+///
+/// The comment isn't posted again if it's already on the discussion, such as when locking failed
+/// in an earlier run.
+async fn lock(
+    clients: Arc<HttpClients>,
+    discussion: &Discussion,
+    options: &LockOptions,
+) -> Result<(), Error> {
+    if let Some(comment) = &options.comment {
+        if has_comment(Arc::clone(&clients), discussion, comment).await? {
+            info!(
+                discussion_url = discussion.url.0,
+                "Closing comment was already posted"
+            );
+        } else {
+            let resp: GraphQlResponse<AddComment> = github_gql_mutation_once(
+                Arc::clone(&clients),
+                AddCommentVariables {
+                    discussion_id: discussion.id.clone(),
+                    body: comment.clone(),
+                },
+            )
+            .await?;
+            check_errors(&resp)?;
+        }
+    }
+
+    let resp: GraphQlResponse<LockDiscussion> = github_gql_mutation(
+        clients,
+        LockDiscussionVariables {
+            discussion_id: discussion.id.clone(),
+        },
+    )
+    .await?;
+    check_errors(&resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{Duration, TimeZone, Utc};
+    use gh_gql_schema::{DateTime, Discussion, DiscussionCategory, Uri};
+
+    use crate::lock::LockOptions;
+
+    fn discussion(title: &str, created_at: &str) -> Discussion {
+        Discussion {
+            id: cynic::Id::new("D_kwDOJSVgjc4ATest"),
+            number: 1,
+            title: title.to_string(),
+            created_at: DateTime(created_at.to_string()),
+            url: Uri("https://github.com/example/comments/discussions/1".to_string()),
+            body: String::new(),
+            body_text: String::new(),
+            locked: false,
+            closed: false,
+            category: DiscussionCategory {
+                id: cynic::Id::new("DIC_kwDOJSVgjc4CVgpt"),
+                name: "Blogs".to_string(),
+                is_answerable: false,
+            },
+        }
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let now = Utc.with_ymd_and_hms(2023, 9, 1, 0, 0, 0).unwrap();
+        let options = LockOptions {
+            evergreen: vec!["/posts/getting-started/".to_string()],
+            ..LockOptions::default()
+        };
+        let published = HashMap::from([
+            ("/posts/old/".to_string(), now - Duration::days(120)),
+            ("/posts/new/".to_string(), now - Duration::days(10)),
+        ]);
+        let is_stale = |d: Discussion| options.is_stale(&d, &published, now);
+
+        let ancient = discussion("/posts/ancient/", "2023-01-01T00:00:00Z");
+        assert!(is_stale(ancient.clone()));
+        assert!(!is_stale(Discussion {
+            locked: true,
+            ..ancient
+        }));
+        assert!(!is_stale(discussion(
+            "/posts/getting-started/",
+            "2023-01-01T00:00:00Z"
+        )));
+
+        // Discussions created recently for an old post are still locked
+        assert!(is_stale(discussion("/posts/old/", "2023-08-20T00:00:00Z")));
+        assert!(!is_stale(discussion("/posts/new/", "2023-08-20T00:00:00Z")));
+
+        // Only the post whose path is exactly the title counts
+        assert!(!is_stale(discussion("/posts", "2023-08-20T00:00:00Z")));
+        assert!(is_stale(discussion("posts/old/", "2023-08-20T00:00:00Z")));
+    }
+}
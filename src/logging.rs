@@ -0,0 +1,26 @@
+use std::env;
+use std::io;
+
+use tracing_subscriber::EnvFilter;
+
+/// Installs a global [`tracing`] subscriber that writes logs to stderr.
+///
+/// Log levels are controlled by `RUST_LOG` (defaulting to `info`), using the
+/// [`EnvFilter`] syntax, such as `RUST_LOG=rss_autogen_giscus=debug`. Setting `LOG_FORMAT=json`
+/// writes one JSON object per event, including the fields of the current spans, for log
+/// aggregation.
+///
+/// Applications embedding the crate can install their own subscriber instead.
+pub fn init_logging() {
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .with_writer(io::stderr);
+
+    match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().init(),
+        Ok("text") | Err(_) => builder.init(),
+        Ok(format) => panic!("LOG_FORMAT must be \"text\" or \"json\", not {format:?}"),
+    }
+}
@@ -1,11 +1,286 @@
-use cynic::http::CynicReqwestError;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
 
-use rss_autogen_giscus::{create_discussion, HttpClients, Post};
+use clap::{Parser, Subcommand, ValueEnum};
+use rss_autogen_giscus::{
+    check_server, find_posts, find_posts_to_update, giscus_snippet, init_logging, lock_stale,
+    preflight, process_post, reconcile, update_post, Action, Error, GitHubActions, HttpClients,
+    LockOptions, ReconcileMode, ReconcileOptions, RunReport, SnippetFormat, SnippetOptions,
+    COMMENTS_EXIST,
+};
+use tracing::{error, info_span, Instrument};
+use url::Url;
+
+/// Autogenerate GitHub Discussions to be used by Giscus. Configuration is read from environment
+/// variables.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Create discussions for new posts if no command is given.
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Format of the run report written to stdout. Logs are always written to stderr.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Also write the JSON run report to this file.
+    #[arg(long, global = true)]
+    output_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Update the bodies of existing discussions whose post's description or URL changed.
+    Update {
+        /// Report the discussions that would be updated, without updating them.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Sitemap listing every post, for sites whose feed only holds recent posts.
+        #[arg(long)]
+        sitemap: Option<Url>,
+    },
+
+    /// Lock the discussions in the category whose comment window has closed.
+    LockStale {
+        /// Lock discussions created, or whose post was published, more than this many days ago.
+        #[arg(long, default_value_t = LockOptions::default().days)]
+        days: i64,
+
+        /// Comment to post before locking each discussion.
+        #[arg(long)]
+        comment: Option<String>,
+
+        /// URL path of a post whose discussion should never be locked. May be repeated.
+        #[arg(long, value_name = "PATH")]
+        evergreen: Vec<String>,
+
+        /// Report the discussions that would be locked, without locking them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Find the discussions whose post was removed from the site.
+    Reconcile {
+        /// What to do with the discussions: report, close (as outdated) or delete.
+        #[arg(long, default_value = "report")]
+        mode: ReconcileMode,
+
+        /// Sitemap listing every post, for sites whose feed only holds recent posts.
+        #[arg(long)]
+        sitemap: Option<Url>,
+
+        /// Also change discussions that weren't created by this program.
+        #[arg(long)]
+        include_foreign: bool,
+    },
+
+    /// Print the Giscus embed snippet for the repository and category. No run report is written,
+    /// the preflight checks are skipped, and only the GitHub settings are read.
+    GiscusSnippet {
+        /// Template to print the snippet for: html, hugo (a partial) or jekyll (an include).
+        #[arg(long, default_value = "html")]
+        format: SnippetFormat,
+
+        /// Category to load discussions from, instead of DISCUSSION_CATEGORY.
+        #[arg(long)]
+        category: Option<String>,
+
+        /// Giscus theme.
+        #[arg(long, default_value_t = SnippetOptions::default().theme)]
+        theme: String,
+
+        /// Giscus interface language.
+        #[arg(long, default_value_t = SnippetOptions::default().lang)]
+        lang: String,
+
+        /// Hide the reactions to the discussion.
+        #[arg(long)]
+        no_reactions: bool,
+
+        /// Show the comment box above the comments.
+        #[arg(long)]
+        input_top: bool,
+
+        /// Only load Giscus when the reader scrolls to the comments.
+        #[arg(long)]
+        lazy: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// No report, only logs.
+    Text,
+
+    /// A JSON report of each post processed.
+    Json,
+}
 
 #[tokio::main]
-pub async fn main() -> Result<(), CynicReqwestError> {
+pub async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    init_logging();
+
+    let command = match cli.command {
+        // Only the snippet is written to stdout, so that it can be redirected to a file
+        Some(Command::GiscusSnippet {
+            format,
+            category,
+            theme,
+            lang,
+            no_reactions,
+            input_top,
+            lazy,
+        }) => {
+            let options = SnippetOptions {
+                format,
+                category,
+                theme,
+                lang,
+                reactions_enabled: !no_reactions,
+                input_top,
+                lazy,
+            };
+            // The feed and posts aren't needed, so neither are their settings
+            let clients = HttpClients::init_github(options.category.clone());
+            return print_snippet(clients, &options).await;
+        }
+        command => command,
+    };
     let clients = HttpClients::init();
-    let latest_post = Post::get_latest(&clients).await?;
+    let mut report = RunReport::default();
+
+    // Log errors with `Display` rather than `Debug`, so that diagnostics such as the preflight
+    // problems are readable
+    if let Err(e) = run(Arc::clone(&clients), command, &mut report).await {
+        error!("{e}");
+        report.errors.push(e.to_string());
+    }
+    report.finish(&clients);
+
+    if cli.output == OutputFormat::Json {
+        report
+            .write_json(io::stdout().lock())
+            .expect("Unable to write report to stdout");
+        println!();
+    }
+    if let Some(path) = &cli.output_file {
+        let file = File::create(path)
+            .unwrap_or_else(|e| panic!("Unable to create report file {}: {e}", path.display()));
+        report
+            .write_json(file)
+            .expect("Unable to write report file");
+    }
+
+    if let Some(actions) = GitHubActions::from_env() {
+        // Keep stdout valid JSON when the report is written there
+        let published = match cli.output {
+            OutputFormat::Json => actions.publish(&report, io::stderr().lock()),
+            OutputFormat::Text => actions.publish(&report, io::stdout().lock()),
+        };
+        if let Err(e) = published {
+            error!("Unable to publish results to GitHub Actions: {e}");
+        }
+    }
+
+    if report.failed() {
+        ExitCode::FAILURE
+    } else if report.posts.iter().any(|p| p.action == Action::Created) {
+        ExitCode::from(COMMENTS_EXIST as u8)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Prints the Giscus embed snippet to stdout.
+async fn print_snippet(clients: Arc<HttpClients>, options: &SnippetOptions) -> ExitCode {
+    match giscus_snippet(clients, options).await {
+        Ok(snippet) => {
+            print!("{snippet}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            error!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(
+    clients: Arc<HttpClients>,
+    command: Option<Command>,
+    report: &mut RunReport,
+) -> Result<(), Error> {
+    let span = info_span!(
+        "run",
+        repo = %format!("{}/{}", clients.repo_owner, clients.repo_name),
+        category = %clients.discussion_category
+    );
+
+    async {
+        // Preflight first, so that a rejected token is reported as such rather than as a failed
+        // request
+        preflight(Arc::clone(&clients)).await?;
+        check_server(&clients).await?;
+        let post_reports = match command {
+            None => {
+                let mut post_reports = Vec::new();
+                for post in find_posts(&clients).await? {
+                    post_reports.push(process_post(Arc::clone(&clients), post).await);
+                }
+                post_reports
+            }
+            Some(Command::Update { dry_run, sitemap }) => {
+                report.dry_run = dry_run;
+                let mut post_reports = Vec::new();
+                for post in find_posts_to_update(Arc::clone(&clients), sitemap.as_ref()).await? {
+                    post_reports.push(update_post(Arc::clone(&clients), post, dry_run).await);
+                }
+                post_reports
+            }
+            Some(Command::LockStale {
+                days,
+                comment,
+                evergreen,
+                dry_run,
+            }) => {
+                report.dry_run = dry_run;
+                let options = LockOptions {
+                    days,
+                    comment,
+                    evergreen,
+                    dry_run,
+                };
+                lock_stale(Arc::clone(&clients), &options).await?
+            }
+            Some(Command::Reconcile {
+                mode,
+                sitemap,
+                include_foreign,
+            }) => {
+                let options = ReconcileOptions {
+                    mode,
+                    sitemap_url: sitemap,
+                    include_foreign,
+                };
+                reconcile(Arc::clone(&clients), &options).await?
+            }
+            Some(Command::GiscusSnippet { .. }) => unreachable!("Handled in main"),
+        };
 
-    create_discussion(clients, latest_post).await
+        for post_report in post_reports {
+            for e in &post_report.errors {
+                error!(post = post_report.url, "{e}");
+            }
+            report.posts.push(post_report);
+        }
+        Ok(())
+    }
+    .instrument(span)
+    .await
 }
@@ -0,0 +1,250 @@
+use std::env;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use cynic::{GraphQlResponse, Id};
+use gh_gql_schema::{
+    AddComment, AddCommentVariables, Discussion, UpdateDiscussionTitle,
+    UpdateDiscussionTitleVariables,
+};
+use tracing::{info, instrument, warn};
+use url::Url;
+
+use crate::embed::is_mapping_title;
+use crate::gql::{
+    category_discussions, check_errors, created_at, github_gql_mutation, github_gql_mutation_once,
+    search_categories,
+};
+use crate::post::discussion_post_url;
+use crate::{Error, HttpClients, Post};
+
+/// How discussions are found and retitled when a post's URL changes, such as after changing the
+/// site's permalink style.
+///
+/// Posts are identified by their [ID](Post::id), which is stored in a hidden comment in the body
+/// of each discussion created by this crate. For older discussions without one, a feed entry ID
+/// that is the post's original URL also matches the discussion titled with that URL's path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MovedPosts {
+    /// Whether the categories of the [duplicate search](HttpClients::duplicate_search) are searched
+    /// for the discussion of a moved post, before creating a new discussion. Off by default, as
+    /// this pages through the discussions of the categories, back to the
+    /// [lookback period](HttpClients::lookback_days).
+    pub enabled: bool,
+
+    /// Comment posted to a discussion after it is retitled. `{old_url}` and `{new_url}` are
+    /// replaced with the post's previous and current URLs.
+    pub comment: Option<String>,
+}
+
+impl MovedPosts {
+    /// Reads the configuration from the `DETECT_MOVED_POSTS` and `MOVED_POST_COMMENT` environment
+    /// variables.
+    pub(crate) fn from_env() -> Self {
+        Self {
+            enabled: env::var("DETECT_MOVED_POSTS")
+                .map(|e| e.parse().expect("DETECT_MOVED_POSTS must be true or false"))
+                .unwrap_or(false),
+            comment: env::var("MOVED_POST_COMMENT").ok(),
+        }
+    }
+}
+
+/// Start of the hidden comment identifying the post in its discussion's body.
+pub(crate) const ID_MARKER: &str = "<!-- rss_autogen_giscus:id";
+
+/// The hidden comment identifying the post in its discussion's body.
+pub(crate) fn id_marker(id: &str) -> String {
+    format!("{ID_MARKER} {} -->", id.replace("-->", "--&gt;"))
+}
+
+/// Whether the discussion belongs to the post, under a previous URL.
+fn is_moved(discussion: &Discussion, post: &Post) -> bool {
+    let id = match &post.id {
+        Some(id) => id,
+        None => return false,
+    };
+    if is_mapping_title(&discussion.title, post.url.path()) {
+        return false;
+    }
+
+    discussion.body.contains(&id_marker(id))
+        || id
+            .parse::<Url>()
+            .map(|original| is_mapping_title(&discussion.title, original.path()))
+            .unwrap_or(false)
+}
+
+/// Searches the categories of the [duplicate search](HttpClients::duplicate_search) for the
+/// discussion of the post under a previous URL, and retitles it with the post's current path.
+#[instrument(skip_all, fields(post = %post.url))]
+pub(crate) async fn rename_moved_discussion(
+    clients: Arc<HttpClients>,
+    post: Arc<Post>,
+    cat_id: Arc<Id>,
+) -> Result<Option<Discussion>, Error> {
+    let now = chrono::Utc::now();
+    let max_lookback = chrono::Duration::days(clients.lookback_days);
+
+    let mut moved = None;
+    for search_id in search_categories(Arc::clone(&clients), cat_id).await? {
+        category_discussions(Arc::clone(&clients), search_id, |discussion| {
+            if !max_lookback.is_zero() && now - created_at(discussion) > max_lookback {
+                return ControlFlow::Break(());
+            } else if is_moved(discussion, &post) {
+                moved = Some(discussion.clone());
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        })
+        .await?;
+        if moved.is_some() {
+            break;
+        }
+    }
+    let discussion = match moved {
+        Some(discussion) => discussion,
+        None => return Ok(None),
+    };
+
+    let old_url = discussion_post_url(&clients, &discussion);
+    let resp: GraphQlResponse<UpdateDiscussionTitle> = github_gql_mutation(
+        Arc::clone(&clients),
+        UpdateDiscussionTitleVariables {
+            discussion_id: discussion.id.clone(),
+            title: post.url.path().to_string(),
+        },
+    )
+    .await?;
+    check_errors(&resp)?;
+    info!(
+        discussion_url = discussion.url.0,
+        old_title = discussion.title,
+        "Renamed discussion of moved post"
+    );
+
+    if let Some(template) = &clients.moved_posts.comment {
+        let body = template
+            .replace("{old_url}", old_url.as_str())
+            .replace("{new_url}", post.url.as_str());
+        if let Err(e) = add_comment(Arc::clone(&clients), &discussion, body).await {
+            warn!("Unable to comment on the renamed discussion: {e}");
+        }
+    }
+
+    Ok(Some(
+        resp.data
+            .and_then(|d| d.update_discussion)
+            .and_then(|payload| payload.discussion)
+            .unwrap_or(Discussion {
+                title: post.url.path().to_string(),
+                ..discussion
+            }),
+    ))
+}
+
+/// Posts the comment, without retrying attempts that may have been applied, so that the comment
+/// isn't posted twice.
+async fn add_comment(
+    clients: Arc<HttpClients>,
+    discussion: &Discussion,
+    body: String,
+) -> Result<(), Error> {
+    let resp: GraphQlResponse<AddComment> = github_gql_mutation_once(
+        clients,
+        AddCommentVariables {
+            discussion_id: discussion.id.clone(),
+            body,
+        },
+    )
+    .await?;
+    check_errors(&resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use gh_gql_schema::{DateTime, Discussion, DiscussionCategory, Uri};
+    use url::Url;
+
+    use crate::moved::{id_marker, is_moved};
+    use crate::Post;
+
+    fn discussion(title: &str, body: &str) -> Discussion {
+        Discussion {
+            id: cynic::Id::new("D_kwDOJSVgjc4ATest"),
+            number: 1,
+            title: title.to_string(),
+            created_at: DateTime("2023-01-01T00:00:00Z".to_string()),
+            url: Uri("https://github.com/example/comments/discussions/1".to_string()),
+            body: body.to_string(),
+            body_text: String::new(),
+            locked: false,
+            closed: false,
+            category: DiscussionCategory {
+                id: cynic::Id::new("DIC_kwDOJSVgjc4CVgpt"),
+                name: "Blogs".to_string(),
+                is_answerable: false,
+            },
+        }
+    }
+
+    fn post(id: &str) -> Post {
+        Post {
+            id: Some(id.to_string()),
+            description: None,
+            url: Url::parse("https://example.com/blog/2023/hello/").unwrap(),
+            tags: Vec::new(),
+            meta: HashMap::new(),
+            giscus: None,
+        }
+    }
+
+    #[test]
+    fn test_is_moved() {
+        let guid = "64858a1f2d3c4b0001a1b2c3";
+        let body = format!(
+            "Hello\n\nhttps://example.com/posts/hello/\n\n{}",
+            id_marker(guid)
+        );
+
+        assert!(is_moved(&discussion("/posts/hello/", &body), &post(guid)));
+        assert!(!is_moved(
+            &discussion("/posts/hello/", &body),
+            &post("another-guid")
+        ));
+        // The discussion already has the current title
+        assert!(!is_moved(
+            &discussion("/blog/2023/hello/", &body),
+            &post(guid)
+        ));
+
+        // Entry IDs that are the original URL of the post
+        assert!(is_moved(
+            &discussion("/posts/hello/", "Hello"),
+            &post("https://example.com/posts/hello/")
+        ));
+        assert!(!is_moved(
+            &discussion("/posts/goodbye/", "Goodbye"),
+            &post("https://example.com/posts/hello/")
+        ));
+        // Titles that are only part of the original path
+        assert!(!is_moved(
+            &discussion("/posts", "Posts"),
+            &post("https://example.com/posts/hello/")
+        ));
+    }
+
+    #[test]
+    fn test_id_marker() {
+        assert_eq!(
+            id_marker("_posts/2023-06-10-hello.md"),
+            "<!-- rss_autogen_giscus:id _posts/2023-06-10-hello.md -->"
+        );
+        assert_eq!(
+            id_marker("odd-->id"),
+            "<!-- rss_autogen_giscus:id odd--&gt;id -->"
+        );
+    }
+}
@@ -1,19 +1,37 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
+use feed_rs::model::Entry;
 use feed_rs::parser::parse;
+use gh_gql_schema::Discussion;
 use scraper::{Html, Selector};
+use tracing::{debug, instrument, warn};
 use url::Url;
 
-use crate::HttpClients;
+use crate::{GiscusScript, HttpClients};
 
 /// A representation of a typical blog post, used in creating the GitHub Discussion
 #[derive(Debug, Clone)]
 pub struct Post {
+    /// ID of the feed entry for the post (such as an RSS `<guid>`), which stays the same if the
+    /// post's URL changes.
+    pub id: Option<String>,
+
     /// Description of the blog post, pulled from the `<meta name="description">` tag.
     pub description: Option<String>,
 
     /// Link to the blog post.
     pub url: Url,
+
+    /// Tags of the post, from the feed entry's categories and the page's
+    /// `<meta property="article:tag">` tags.
+    pub tags: Vec<String>,
+
+    /// Content of the page's `<meta>` tags, keyed by their `name` or `property`.
+    pub meta: HashMap<String, String>,
+
+    /// Settings of the page's Giscus `<script>` tag, if it has one.
+    pub giscus: Option<GiscusScript>,
 }
 
 impl Post {
@@ -40,71 +58,205 @@ impl Post {
     ///     assert!(latest_post.url.as_str().contains(BASE_URL));
     ///     Ok(())
     /// }
+    #[instrument(skip_all)]
     pub async fn get_latest(clients: &HttpClients) -> reqwest::Result<Arc<Self>> {
-        let post_url = latest_post_from_rss(clients).await?;
+        let (post_url, entry) = latest_post_from_rss(clients).await?;
+        Ok(Arc::new(Self::from_entry(clients, post_url, entry).await?))
+    }
 
+    /// Extracts the description and tags from the blog post of a feed entry, at `post_url`. The
+    /// entry's categories are added to the tags.
+    pub(crate) async fn from_entry(
+        clients: &HttpClients,
+        post_url: Url,
+        entry: Entry,
+    ) -> reqwest::Result<Self> {
+        let mut post = Self::from_url(clients, post_url, Some(entry.id)).await?;
+
+        for category in entry.categories {
+            if !post.tags.contains(&category.term) {
+                post.tags.push(category.term);
+            }
+        }
+        Ok(post)
+    }
+
+    /// Extracts the description and tags from the blog post at the URL.
+    #[instrument(skip_all, fields(post = %post_url))]
+    pub async fn from_url(
+        clients: &HttpClients,
+        post_url: Url,
+        id: Option<String>,
+    ) -> reqwest::Result<Self> {
         let desc_selector = Selector::parse("meta[name=\"description\"]").unwrap();
-        let post = Html::parse_document(
-            &clients
-                .html
-                .get(post_url.clone())
-                .send()
-                .await?
-                .text()
-                .await?,
-        );
+        let tag_selector = Selector::parse("meta[property=\"article:tag\"]").unwrap();
+        let meta_selector = Selector::parse("meta[content]").unwrap();
+        let giscus_selector = Selector::parse("script[src*=\"giscus.app\"]").unwrap();
+        let post =
+            Html::parse_document(&String::from_utf8_lossy(&fetch(clients, &post_url).await?));
 
         let desc_element = post.select(&desc_selector).next();
+        if desc_element.is_none() {
+            warn!(post = %post_url, "No description was found in the post");
+        }
 
-        Ok(Arc::new(Self {
+        Ok(Self {
+            id,
             description: desc_element
                 .and_then(|el| el.value().attr("content"))
                 .map(|desc| desc.to_string()),
             url: post_url,
-        }))
+            tags: post
+                .select(&tag_selector)
+                .filter_map(|el| el.value().attr("content"))
+                .map(|tag| tag.to_string())
+                .collect(),
+            meta: post
+                .select(&meta_selector)
+                .filter_map(|el| {
+                    let el = el.value();
+                    let name = el.attr("name").or_else(|| el.attr("property"))?;
+                    Some((name.to_string(), el.attr("content")?.to_string()))
+                })
+                .collect(),
+            giscus: post.select(&giscus_selector).next().map(|el| {
+                let attr = |name| el.value().attr(name).map(str::to_string);
+                GiscusScript {
+                    repo: attr("data-repo"),
+                    repo_id: attr("data-repo-id"),
+                    category: attr("data-category"),
+                    category_id: attr("data-category-id"),
+                    mapping: attr("data-mapping"),
+                    strict: attr("data-strict"),
+                }
+            }),
+        })
     }
 }
 
-/// Retrieves the latest blog post from [the website's RSS feed](HttpClients::website_rss_url).
-async fn latest_post_from_rss(clients: &HttpClients) -> reqwest::Result<Url> {
-    let rss_response = clients
-        .html
-        .get(&clients.website_rss_url)
+/// Retrieves the URL and feed entry of the latest blog post from
+/// [the website's RSS feed](HttpClients::website_rss_url).
+async fn latest_post_from_rss(clients: &HttpClients) -> reqwest::Result<(Url, Entry)> {
+    match feed_entries(clients)
+        .await?
+        .into_iter()
+        .next()
+        .and_then(|post| Some((post.links.first()?.href.parse().unwrap(), post)))
+    {
+        Some(latest) => Ok(latest),
+        None => panic!("Unable to retrieve link to latest post from feed"),
+    }
+}
+
+/// Retrieves the entries of [the website's RSS feed](HttpClients::website_rss_url), in feed order.
+pub(crate) async fn feed_entries(clients: &HttpClients) -> reqwest::Result<Vec<Entry>> {
+    let rss_url: Url = clients
+        .website_rss_url
+        .parse()
+        .expect("WEBSITE_RSS_URL is not a valid URL");
+    let rss_response = fetch(clients, &rss_url).await?;
+    Ok(parse(rss_response.as_slice())
+        .expect("Unable to parse feed")
+        .entries)
+}
+
+/// The URL of the post that Giscus maps to a discussion, from the discussion's title (the post's
+/// path) and the origin of [the website's RSS feed](HttpClients::website_rss_url).
+pub(crate) fn discussion_post_url(clients: &HttpClients, discussion: &Discussion) -> Url {
+    let mut post_url: Url = clients
+        .website_rss_url
+        .parse()
+        .expect("WEBSITE_RSS_URL is not a valid URL");
+    // The title is a full path, which may not start with a slash (see `pathname_term`)
+    post_url.set_path(&discussion.title);
+    post_url.set_query(None);
+    post_url.set_fragment(None);
+    post_url
+}
+
+/// Retrieves the body of a page with the [HTML client](HttpClients::html), applying the
+/// [site options and pacing](HttpClients::scrape_config) for its host.
+///
+/// During tests, responses are recorded and replayed from fixtures (see [`crate::fixtures`]).
+pub(crate) async fn fetch(clients: &HttpClients, url: &Url) -> reqwest::Result<Vec<u8>> {
+    debug!(%url, "Fetching page");
+
+    #[cfg(test)]
+    if let Some(body) = crate::fixtures::replay(url) {
+        return Ok(body);
+    }
+
+    clients.scrape_config.pace(url).await;
+    let body = clients
+        .scrape_config
+        .get(&clients.html, url)
         .send()
         .await?
+        .error_for_status()?
         .bytes()
         .await?;
-    let feed = parse(&*rss_response).expect("Unable to parse feed");
 
-    match feed
-        .entries
-        .first()
-        .and_then(|post| post.links.first())
-        .map(|link| link.href.as_str())
-    {
-        Some(latest_url) => Ok(latest_url.parse().unwrap()),
-        None => panic!("Unable to retrieve link to latest post from feed"),
-    }
+    #[cfg(test)]
+    crate::fixtures::record(url, &body);
+
+    Ok(body.to_vec())
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
+    use gh_gql_schema::{DateTime, Discussion, DiscussionCategory, Uri};
     use tokio_test::assert_ok;
 
-    use crate::post::latest_post_from_rss;
-    use crate::{HttpClients, Post};
+    use crate::post::{discussion_post_url, latest_post_from_rss};
+    use crate::{GiscusScript, HttpClients, Post};
 
     const CPLX_RSS_FEED: &str = "https://rss.cbc.ca/lineup/topstories.xml";
 
+    /// Feeds from common blogging platforms, with the latest post and its expected description.
+    ///
+    /// Responses are replayed from `tests/fixtures`.
+    const FEED_CORPUS: &[(&str, &str, Option<&str>)] = &[
+        (
+            "https://team-role-org-testing.github.io/feed.xml",
+            "https://team-role-org-testing.github.io/jekyll/update/2023/04/03/welcome-to-jekyll.html",
+            Some("You’ll find this post in your _posts directory. Go ahead and edit it and re-build the site to see your changes. You can rebuild the site in many different ways, but the most common way is to run jekyll serve, which launches a web server and auto-regenerates your site when a file is updated."),
+        ),
+        (
+            "https://hugo.example.com/index.xml",
+            "https://hugo.example.com/posts/hugo-modules/",
+            Some("Hugo modules make it easy to share themes and components between sites."),
+        ),
+        (
+            "https://ghost.example.com/rss/",
+            "https://ghost.example.com/writing-posts-with-cards/",
+            Some("Cards let you add images, embeds and more to your posts."),
+        ),
+        (
+            "https://wordpress.example.com/feed/",
+            "https://wordpress.example.com/2023/06/10/gardening-composting-small-spaces/",
+            Some("A balcony is all you need to start composting & growing herbs year-round."),
+        ),
+        (
+            "https://example.substack.com/feed",
+            "https://example.substack.com/p/issue-42-why-examples-matter",
+            Some("Plus: three things I learned writing documentation this week"),
+        ),
+        (
+            "https://jsonfeed.example.com/feed.json",
+            "https://jsonfeed.example.com/2023/06/08/short-note.html",
+            None,
+        ),
+    ];
+
     #[tokio::test]
     async fn test_get_post_url() {
         let clients = HttpClients::test_setup(true);
         let post = latest_post_from_rss(&clients).await;
 
         assert_ok!(&post);
-        println!("{}", post.unwrap());
+        println!("{}", post.unwrap().0);
     }
 
     /// Try to pull the latest post from a more active RSS feed
@@ -117,7 +269,7 @@ mod tests {
         let post = latest_post_from_rss(&clients).await;
 
         assert_ok!(&post);
-        println!("{}", post.unwrap());
+        println!("{}", post.unwrap().0);
     }
 
     #[tokio::test]
@@ -131,6 +283,39 @@ mod tests {
         latest_post_from_rss(&clients).await.unwrap();
     }
 
+    #[test]
+    fn test_discussion_post_url() {
+        let clients = HttpClients {
+            website_rss_url: "https://hugo.example.com/blog/index.xml?format=rss".to_string(),
+            ..HttpClients::test_setup(true)
+        };
+        let discussion = |title: &str| Discussion {
+            id: cynic::Id::new("D_kwDOJSVgjc4ATest"),
+            number: 1,
+            title: title.to_string(),
+            created_at: DateTime("2023-01-01T00:00:00Z".to_string()),
+            url: Uri("https://github.com/example/comments/discussions/1".to_string()),
+            body: String::new(),
+            body_text: String::new(),
+            locked: false,
+            closed: false,
+            category: DiscussionCategory {
+                id: cynic::Id::new("DIC_kwDOJSVgjc4CVgpt"),
+                name: "Blogs".to_string(),
+                is_answerable: false,
+            },
+        };
+
+        assert_eq!(
+            discussion_post_url(&clients, &discussion("/posts/hugo-modules/")).as_str(),
+            "https://hugo.example.com/posts/hugo-modules/"
+        );
+        assert_eq!(
+            discussion_post_url(&clients, &discussion("posts/hugo-modules")).as_str(),
+            "https://hugo.example.com/posts/hugo-modules"
+        );
+    }
+
     #[tokio::test]
     async fn test_extract_post_details() {
         let clients = HttpClients::test_setup(true);
@@ -146,6 +331,92 @@ mod tests {
         post_details_internal(clients, "www.cbc.ca").await;
     }
 
+    #[tokio::test]
+    async fn test_corpus_latest_post_url() {
+        for (feed_url, latest_url, _) in FEED_CORPUS {
+            let clients = HttpClients {
+                website_rss_url: feed_url.to_string(),
+                ..HttpClients::test_setup(true)
+            };
+            let post_url = latest_post_from_rss(&clients).await;
+
+            assert_ok!(&post_url);
+            assert_eq!(
+                post_url.unwrap().0.as_str(),
+                *latest_url,
+                "Feed: {feed_url}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_corpus_descriptions() {
+        for (feed_url, latest_url, description) in FEED_CORPUS {
+            let clients = HttpClients {
+                website_rss_url: feed_url.to_string(),
+                ..HttpClients::test_setup(true)
+            };
+            let post = Post::get_latest(&clients).await;
+
+            assert_ok!(&post);
+            let post = post.unwrap();
+            assert_eq!(post.url.as_str(), *latest_url);
+            assert_eq!(
+                post.description.as_deref(),
+                *description,
+                "Post: {latest_url}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_corpus_tags() {
+        for (feed_url, tags) in [
+            ("https://ghost.example.com/rss/", &["Getting Started"][..]),
+            (
+                "https://hugo.example.com/index.xml",
+                &["hugo", "static-sites"],
+            ),
+            (
+                "https://wordpress.example.com/feed/",
+                &["Gardening", "How-to"],
+            ),
+        ] {
+            let clients = HttpClients {
+                website_rss_url: feed_url.to_string(),
+                ..HttpClients::test_setup(true)
+            };
+            let post = Post::get_latest(&clients).await.unwrap();
+            assert_eq!(post.tags, tags, "Feed: {feed_url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_giscus_script() {
+        let clients = HttpClients {
+            website_rss_url: "https://hugo.example.com/index.xml".to_string(),
+            ..HttpClients::test_setup(true)
+        };
+        let post = Post::get_latest(&clients).await.unwrap();
+        assert_eq!(
+            post.giscus,
+            Some(GiscusScript {
+                repo: Some("hugo-example/comments".to_string()),
+                repo_id: Some("R_kgDOJHugo1".to_string()),
+                category: Some("Posts".to_string()),
+                category_id: Some("DIC_kwDOJHugo14CVgpt".to_string()),
+                mapping: Some("pathname".to_string()),
+                strict: Some("0".to_string()),
+            })
+        );
+
+        let clients = HttpClients {
+            website_rss_url: "https://ghost.example.com/rss/".to_string(),
+            ..HttpClients::test_setup(true)
+        };
+        assert_eq!(Post::get_latest(&clients).await.unwrap().giscus, None);
+    }
+
     async fn post_details_internal(clients: HttpClients, post_domain: &str) {
         let post = Post::get_latest(&clients).await;
 
@@ -0,0 +1,311 @@
+use std::sync::Arc;
+
+use cynic::GraphQlResponse;
+use gh_gql_schema::{PreflightQuery, PreflightQueryVariables, RepositoryPermission};
+use reqwest::header::ACCEPT;
+use reqwest::StatusCode;
+
+use crate::gql::github_gql_query;
+use crate::{DuplicateSearch, Error, HttpClients};
+
+/// Classic personal access token scopes that allow creating discussions.
+const DISCUSSION_SCOPES: [&str; 3] = ["repo", "public_repo", "write:discussion"];
+
+/// Slug of the announcement category GitHub creates with discussions. The API doesn't expose a
+/// category's format, so other announcement categories can't be detected.
+const ANNOUNCEMENTS_SLUG: &str = "announcements";
+
+/// Problem reported when GitHub responds with `401 Unauthorized`.
+const TOKEN_REJECTED: &str = "The token was rejected by GitHub.";
+
+/// Checks that the token and repository are configured correctly before creating a discussion,
+/// so that misconfiguration is reported up front rather than as a failure partway through a run.
+///
+/// This checks:
+///
+/// - the scopes of classic personal access tokens (from the `X-OAuth-Scopes` header)
+/// - that the repository is accessible to the token, and has discussions enabled
+/// - that the [discussion category](HttpClients::discussion_category), and every category posts
+///   are [routed](HttpClients::category_routes) to, exists, and isn't the default announcement
+///   category when the token lacks maintainer access
+/// - that the categories of the [duplicate search](HttpClients::duplicate_search) exist
+///
+/// Every problem found is reported together in [`Error::Preflight`].
+pub async fn preflight(clients: Arc<HttpClients>) -> Result<(), Error> {
+    let mut problems = token_scope_problems(&clients).await?;
+
+    let preflight_resp: Result<GraphQlResponse<PreflightQuery>, Error> = github_gql_query(
+        Arc::clone(&clients),
+        PreflightQueryVariables {
+            owner: &clients.repo_owner,
+            repo_name: &clients.repo_name,
+        },
+    )
+    .await;
+
+    match preflight_resp {
+        Ok(resp) => match resp.data.and_then(|d| d.repository) {
+            Some(repo) => problems.extend(repository_problems(&clients, &repo)),
+            None => problems.push(format!(
+                "Repository {}/{} could not be found, or the token can't access it.{}",
+                clients.repo_owner,
+                clients.repo_name,
+                resp.errors
+                    .iter()
+                    .flatten()
+                    .map(|e| format!(" {}", e.message))
+                    .collect::<String>()
+            )),
+        },
+        // Already reported by the REST request
+        Err(Error::Unauthorized(_)) if problems.iter().any(|p| p == TOKEN_REJECTED) => {}
+        Err(Error::Unauthorized(_)) => problems.push(TOKEN_REJECTED.to_string()),
+        Err(e) => problems.push(format!(
+            "Unable to retrieve the repository from the GraphQL API: {e}"
+        )),
+    }
+
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(Error::Preflight(problems)),
+    }
+}
+
+/// Checks the scopes of classic personal access tokens. Fine-grained tokens and GitHub App
+/// tokens don't report scopes, and are checked through the repository permissions instead.
+///
+/// Only fails when no token could be obtained.
+async fn token_scope_problems(clients: &HttpClients) -> Result<Vec<String>, Error> {
+    let resp = match clients
+        .html
+        .get(format!(
+            "{}/repos/{}/{}",
+            clients.github_rest_url, clients.repo_owner, clients.repo_name
+        ))
+        .bearer_auth(clients.token_provider.token(clients).await?)
+        .header(ACCEPT, "application/vnd.github+json")
+        .send()
+        .await
+    {
+        Ok(resp) => resp,
+        Err(e) => {
+            return Ok(vec![format!(
+                "Unable to retrieve the repository from the REST API: {e}"
+            )])
+        }
+    };
+
+    let mut problems = Vec::new();
+    match resp.status() {
+        StatusCode::UNAUTHORIZED => problems.push(TOKEN_REJECTED.to_string()),
+        status if !status.is_success() && status != StatusCode::NOT_FOUND => problems.push(
+            format!("Unable to retrieve the repository from the REST API ({status})."),
+        ),
+        _ => {}
+    }
+
+    if let Some(scopes) = resp
+        .headers()
+        .get("x-oauth-scopes")
+        .and_then(|v| v.to_str().ok())
+    {
+        if !scopes
+            .split(',')
+            .any(|scope| DISCUSSION_SCOPES.contains(&scope.trim()))
+        {
+            problems.push(format!(
+                "The token's scopes ({}) don't allow creating discussions. It requires one of: {}.",
+                match scopes.trim() {
+                    "" => "none",
+                    scopes => scopes,
+                },
+                DISCUSSION_SCOPES.join(", ")
+            ));
+        }
+    }
+    Ok(problems)
+}
+
+/// Checks the repository settings, token permissions and discussion categories.
+fn repository_problems(
+    clients: &HttpClients,
+    repo: &gh_gql_schema::PreflightQueryRepository,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    if !repo.has_discussions_enabled {
+        problems.push(format!(
+            "Discussions are not enabled in {}/{}.",
+            clients.repo_owner, clients.repo_name
+        ));
+    }
+    if repo.viewer_permission.is_none() {
+        problems.push("The token has no permissions on the repository.".to_string());
+    }
+
+    let categories: Vec<&str> = repo
+        .discussion_categories
+        .nodes
+        .iter()
+        .map(|cat| cat.name.as_str())
+        .collect();
+    for category in clients
+        .category_routes
+        .categories(&clients.discussion_category)
+    {
+        if !categories.contains(&category) {
+            problems.push(format!(
+                "Category {} was not present in repository {}/{}. Available categories: {}.",
+                category,
+                clients.repo_owner,
+                clients.repo_name,
+                categories.join(", ")
+            ));
+        } else if repo
+            .discussion_categories
+            .nodes
+            .iter()
+            .any(|cat| cat.name == category && cat.slug == ANNOUNCEMENTS_SLUG)
+            && repo.viewer_permission < Some(RepositoryPermission::Maintain)
+        {
+            problems.push(format!(
+                "Category {} is an announcement category, which only maintainers and admins can post in, but the token only has {:?} permission.",
+                category,
+                repo.viewer_permission.unwrap_or(RepositoryPermission::Read)
+            ));
+        }
+    }
+    if let DuplicateSearch::Categories(searched) = &clients.duplicate_search {
+        for category in searched {
+            if !categories.contains(&category.as_str()) {
+                problems.push(format!(
+                    "Category {} in DUPLICATE_SEARCH_CATEGORIES was not present in repository {}/{}.",
+                    category, clients.repo_owner, clients.repo_name
+                ));
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use gh_gql_schema::{
+        PreflightCategory, PreflightCategoryConnection, PreflightQueryRepository,
+        RepositoryPermission,
+    };
+
+    use crate::preflight::repository_problems;
+    use crate::{CategoryRoute, CategoryRoutes, DuplicateSearch, HttpClients};
+
+    fn repository(
+        has_discussions_enabled: bool,
+        viewer_permission: Option<RepositoryPermission>,
+        categories: &[&str],
+    ) -> PreflightQueryRepository {
+        PreflightQueryRepository {
+            has_discussions_enabled,
+            viewer_permission,
+            discussion_categories: PreflightCategoryConnection {
+                nodes: categories
+                    .iter()
+                    .map(|name| PreflightCategory {
+                        name: name.to_string(),
+                        slug: name
+                            .to_lowercase()
+                            .replace(|c: char| !c.is_alphanumeric(), "-"),
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_valid_repository() {
+        let clients = HttpClients::test_setup(true);
+        let repo = repository(true, Some(RepositoryPermission::Write), &["Blogs", "Q&A"]);
+
+        assert!(repository_problems(&clients, &repo).is_empty());
+    }
+
+    #[test]
+    fn test_all_problems_reported() {
+        let clients = HttpClients::test_setup(true);
+        let repo = repository(false, None, &["General", "Q&A"]);
+
+        let problems = repository_problems(&clients, &repo);
+        assert_eq!(problems.len(), 3, "{problems:#?}");
+        assert!(problems[2].contains("Available categories: General, Q&A"));
+    }
+
+    #[test]
+    fn test_announcement_category() {
+        let clients = HttpClients {
+            discussion_category: "Announcements".to_string(),
+            ..HttpClients::test_setup(true)
+        };
+
+        let repo = repository(true, Some(RepositoryPermission::Write), &["Announcements"]);
+        assert_eq!(repository_problems(&clients, &repo).len(), 1);
+
+        let repo = repository(
+            true,
+            Some(RepositoryPermission::Maintain),
+            &["Announcements"],
+        );
+        assert!(repository_problems(&clients, &repo).is_empty());
+
+        // Detected by the slug rather than the name
+        let clients = HttpClients {
+            discussion_category: "News".to_string(),
+            ..HttpClients::test_setup(true)
+        };
+        let mut repo = repository(true, Some(RepositoryPermission::Write), &["News"]);
+        assert!(repository_problems(&clients, &repo).is_empty());
+        repo.discussion_categories.nodes[0].slug = "announcements".to_string();
+        assert_eq!(repository_problems(&clients, &repo).len(), 1);
+    }
+
+    #[test]
+    fn test_routed_categories() {
+        let clients = HttpClients {
+            category_routes: CategoryRoutes::new(vec![CategoryRoute {
+                category: "Notes".to_string(),
+                path_prefix: Some("/notes/".to_string()),
+                path_regex: None,
+                tag: None,
+                meta: HashMap::new(),
+            }]),
+            ..HttpClients::test_setup(true)
+        };
+
+        let repo = repository(true, Some(RepositoryPermission::Write), &["Blogs", "Notes"]);
+        assert!(repository_problems(&clients, &repo).is_empty());
+
+        let repo = repository(true, Some(RepositoryPermission::Write), &["Blogs"]);
+        let problems = repository_problems(&clients, &repo);
+        assert_eq!(problems.len(), 1, "{problems:#?}");
+        assert!(problems[0].starts_with("Category Notes was not present"));
+    }
+
+    #[test]
+    fn test_duplicate_search_categories() {
+        let clients = HttpClients {
+            duplicate_search: DuplicateSearch::Categories(vec!["General".to_string()]),
+            ..HttpClients::test_setup(true)
+        };
+
+        let repo = repository(
+            true,
+            Some(RepositoryPermission::Write),
+            &["Blogs", "General"],
+        );
+        assert!(repository_problems(&clients, &repo).is_empty());
+
+        let repo = repository(true, Some(RepositoryPermission::Write), &["Blogs"]);
+        let problems = repository_problems(&clients, &repo);
+        assert_eq!(problems.len(), 1, "{problems:#?}");
+        assert!(problems[0].starts_with("Category General in DUPLICATE_SEARCH_CATEGORIES"));
+    }
+}
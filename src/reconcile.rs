@@ -0,0 +1,374 @@
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use cynic::GraphQlResponse;
+use gh_gql_schema::{
+    CloseDiscussion, CloseDiscussionVariables, DeleteDiscussion, DeleteDiscussionVariables,
+    Discussion,
+};
+use reqwest::StatusCode;
+use scraper::{Html, Selector};
+use tracing::{debug, info, instrument};
+use url::Url;
+
+use crate::embed::is_mapping_title;
+use crate::gql::{category_discussions, check_errors, github_gql_mutation};
+use crate::moved::ID_MARKER;
+use crate::post::{discussion_post_url, feed_entries, fetch};
+use crate::routing::category_id;
+use crate::{Action, Error, HttpClients, PostReport};
+
+/// What [`reconcile`] does with the discussions of removed posts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReconcileMode {
+    /// Only report the discussions, as [`Action::Orphaned`].
+    #[default]
+    Report,
+
+    /// Close the discussions as outdated, keeping their comments visible on GitHub.
+    Close,
+
+    /// Delete the discussions, along with their comments.
+    Delete,
+}
+
+impl FromStr for ReconcileMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "report" => Ok(Self::Report),
+            "close" => Ok(Self::Close),
+            "delete" => Ok(Self::Delete),
+            _ => Err(format!(
+                "Unknown reconcile mode {s}, expected report, close or delete"
+            )),
+        }
+    }
+}
+
+/// Options for [`reconcile`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReconcileOptions {
+    /// What is done with the discussions of removed posts.
+    pub mode: ReconcileMode,
+
+    /// Sitemap listing every post. Feeds usually only hold the most recent posts, so without a
+    /// sitemap, the page of every older post is requested to check whether it was removed.
+    pub sitemap_url: Option<Url>,
+
+    /// Also act on discussions that weren't created by this crate.
+    pub include_foreign: bool,
+}
+
+/// Finds the discussions in the categories whose post was removed from the site, and reports,
+/// closes or deletes them according to the [mode](ReconcileOptions::mode).
+///
+/// A post is removed if it isn't in the feed or sitemap, and its page responds with
+/// `404 Not Found` or `410 Gone`.
+#[instrument(skip_all, fields(mode = ?options.mode))]
+pub async fn reconcile(
+    clients: Arc<HttpClients>,
+    options: &ReconcileOptions,
+) -> Result<Vec<PostReport>, Error> {
+    let mut post_paths: HashSet<String> = feed_entries(&clients)
+        .await?
+        .iter()
+        .filter_map(|entry| entry.links.first()?.href.parse::<Url>().ok())
+        .map(|url| url.path().to_string())
+        .collect();
+    if let Some(sitemap_url) = &options.sitemap_url {
+        post_paths.extend(
+            sitemap_urls(&clients, sitemap_url)
+                .await?
+                .iter()
+                .map(|url| url.path().to_string()),
+        );
+    }
+
+    let mut candidates = Vec::new();
+    for category in clients
+        .category_routes
+        .categories(&clients.discussion_category)
+    {
+        let cat_id = category_id(Arc::clone(&clients), category).await?;
+        category_discussions(Arc::clone(&clients), Some(cat_id), |discussion| {
+            if options.is_candidate(discussion, &post_paths) {
+                candidates.push(discussion.clone());
+            }
+            ControlFlow::Continue(())
+        })
+        .await?;
+    }
+
+    let mut reports = Vec::new();
+    for discussion in candidates {
+        let post_url = discussion_post_url(&clients, &discussion);
+        let action = match options.mode {
+            ReconcileMode::Report => Action::Orphaned,
+            ReconcileMode::Close => Action::Closed,
+            ReconcileMode::Delete => Action::Deleted,
+        };
+        let mut report = PostReport::for_discussion(&post_url, &discussion, action);
+
+        match page_removed(&clients, &post_url).await {
+            Ok(false) => {
+                debug!(post = %post_url, "Post is missing from the feed, but its page exists");
+                continue;
+            }
+            Ok(true) => {
+                if let Err(e) = apply(Arc::clone(&clients), &discussion, options.mode).await {
+                    report.action = Action::Failed;
+                    report.errors.push(e.to_string());
+                } else {
+                    info!(
+                        discussion_url = discussion.url.0,
+                        ?action,
+                        "Post was removed"
+                    );
+                }
+            }
+            Err(e) => {
+                report.action = Action::Failed;
+                report.errors.push(e.to_string());
+            }
+        }
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+impl ReconcileOptions {
+    /// Whether the discussion's post is missing from `post_paths`, and the discussion can be
+    /// changed.
+    fn is_candidate(&self, discussion: &Discussion, post_paths: &HashSet<String>) -> bool {
+        let foreign = !self.include_foreign && !created_by_crate(discussion);
+        let closed = discussion.closed && self.mode != ReconcileMode::Delete;
+
+        !foreign
+            && !closed
+            && !post_paths
+                .iter()
+                .any(|path| is_mapping_title(&discussion.title, path))
+    }
+}
+
+/// Whether the discussion was created by this crate, whose discussion bodies carry the post ID
+/// marker or a line linking to the post (see [`discussion_body`](crate::gql::discussion_body)).
+/// Hand-edited sections may follow that line.
+fn created_by_crate(discussion: &Discussion) -> bool {
+    discussion.body.lines().map(str::trim).any(|line| {
+        line.starts_with(ID_MARKER)
+            || line
+                .parse::<Url>()
+                .map(|url| url.path() == discussion.title)
+                .unwrap_or(false)
+    })
+}
+
+/// Retrieves the URLs of the pages in a sitemap, following a sitemap index one level deep.
+pub(crate) async fn sitemap_urls(
+    clients: &HttpClients,
+    sitemap_url: &Url,
+) -> Result<Vec<Url>, Error> {
+    let index_selector = Selector::parse("sitemapindex > sitemap > loc").unwrap();
+    let page_selector = Selector::parse("urlset > url > loc").unwrap();
+    let locations = |sitemap: &Html, selector: &Selector| -> Vec<Url> {
+        sitemap
+            .select(selector)
+            .filter_map(|loc| loc.text().collect::<String>().trim().parse().ok())
+            .collect()
+    };
+
+    let sitemap = Html::parse_document(&String::from_utf8_lossy(
+        &fetch(clients, sitemap_url).await?,
+    ));
+    let mut pages = locations(&sitemap, &page_selector);
+    for child_url in locations(&sitemap, &index_selector) {
+        let child =
+            Html::parse_document(&String::from_utf8_lossy(&fetch(clients, &child_url).await?));
+        pages.extend(locations(&child, &page_selector));
+    }
+
+    Ok(pages)
+}
+
+/// Checks whether the post's page is gone, rather than just missing from the feed.
+async fn page_removed(clients: &HttpClients, post_url: &Url) -> reqwest::Result<bool> {
+    clients.scrape_config.pace(post_url).await;
+    let status = clients
+        .scrape_config
+        .get(&clients.html, post_url)
+        .send()
+        .await?
+        .status();
+    Ok(matches!(status, StatusCode::NOT_FOUND | StatusCode::GONE))
+}
+
+/// Closes or deletes the discussion, depending on the mode.
+async fn apply(
+    clients: Arc<HttpClients>,
+    discussion: &Discussion,
+    mode: ReconcileMode,
+) -> Result<(), Error> {
+    match mode {
+        ReconcileMode::Report => Ok(()),
+        ReconcileMode::Close => {
+            let resp: GraphQlResponse<CloseDiscussion> = github_gql_mutation(
+                clients,
+                CloseDiscussionVariables {
+                    discussion_id: discussion.id.clone(),
+                },
+            )
+            .await?;
+            check_errors(&resp)
+        }
+        ReconcileMode::Delete => {
+            let resp: GraphQlResponse<DeleteDiscussion> = github_gql_mutation(
+                clients,
+                DeleteDiscussionVariables {
+                    discussion_id: discussion.id.clone(),
+                },
+            )
+            .await?;
+            check_errors(&resp)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use gh_gql_schema::{DateTime, Discussion, DiscussionCategory, Uri};
+    use url::Url;
+
+    use crate::reconcile::{created_by_crate, sitemap_urls, ReconcileMode, ReconcileOptions};
+    use crate::{HttpClients, KEEP_END, KEEP_START};
+
+    fn discussion(title: &str, body: &str) -> Discussion {
+        Discussion {
+            id: cynic::Id::new("D_kwDOJSVgjc4ATest"),
+            number: 1,
+            title: title.to_string(),
+            created_at: DateTime("2023-01-01T00:00:00Z".to_string()),
+            url: Uri("https://github.com/example/comments/discussions/1".to_string()),
+            body: body.to_string(),
+            body_text: body.to_string(),
+            locked: false,
+            closed: false,
+            category: DiscussionCategory {
+                id: cynic::Id::new("DIC_kwDOJSVgjc4CVgpt"),
+                name: "Blogs".to_string(),
+                is_answerable: false,
+            },
+        }
+    }
+
+    #[test]
+    fn test_created_by_crate() {
+        assert!(created_by_crate(&discussion(
+            "/posts/hello/",
+            "My first post.\n\nhttps://example.com/posts/hello/"
+        )));
+        assert!(created_by_crate(&discussion(
+            "/posts/hello/",
+            "https://example.com/posts/hello/\n"
+        )));
+        assert!(created_by_crate(&discussion(
+            "/posts/hello/",
+            &format!("https://example.com/posts/hello/\n\n{KEEP_START}\nSee also the sequel.\n{KEEP_END}")
+        )));
+        assert!(created_by_crate(&discussion(
+            "/posts/renamed/",
+            "https://example.com/posts/hello/\n\n<!-- rss_autogen_giscus:id tag:example.com,2023:1 -->"
+        )));
+        assert!(!created_by_crate(&discussion(
+            "/posts/hello/",
+            "Welcome to the comments for this site!"
+        )));
+        assert!(!created_by_crate(&discussion(
+            "/posts/hello/",
+            "Moved to https://example.com/posts/goodbye/"
+        )));
+    }
+
+    #[test]
+    fn test_is_candidate() {
+        let post_paths = HashSet::from(["/posts/hello/".to_string()]);
+        let removed = discussion("/posts/removed/", "https://example.com/posts/removed/");
+        let options = ReconcileOptions::default();
+
+        assert!(options.is_candidate(&removed, &post_paths));
+        assert!(!options.is_candidate(
+            &discussion("/posts/hello/", "https://example.com/posts/hello/"),
+            &post_paths
+        ));
+        assert!(!options.is_candidate(&discussion("/posts/removed/", "General chat"), &post_paths));
+        // Only an exact Giscus mapping keeps the discussion, not any substring of a path
+        assert!(options.is_candidate(
+            &discussion("/posts/", "https://example.com/posts/"),
+            &post_paths
+        ));
+        assert!(!ReconcileOptions {
+            include_foreign: true,
+            ..ReconcileOptions::default()
+        }
+        .is_candidate(&discussion("posts/hello/", "General chat"), &post_paths));
+        assert!(ReconcileOptions {
+            include_foreign: true,
+            ..ReconcileOptions::default()
+        }
+        .is_candidate(&discussion("/posts/removed/", "General chat"), &post_paths));
+
+        let closed = Discussion {
+            closed: true,
+            ..removed
+        };
+        assert!(!options.is_candidate(&closed, &post_paths));
+        assert!(ReconcileOptions {
+            mode: ReconcileMode::Delete,
+            ..ReconcileOptions::default()
+        }
+        .is_candidate(&closed, &post_paths));
+    }
+
+    #[tokio::test]
+    async fn test_sitemap_urls() {
+        let clients = HttpClients::test_setup(true);
+        let to_paths = |urls: Vec<Url>| -> Vec<String> {
+            urls.iter().map(|url| url.path().to_string()).collect()
+        };
+
+        let paths = to_paths(
+            sitemap_urls(
+                &clients,
+                &Url::parse("https://hugo.example.com/sitemap.xml").unwrap(),
+            )
+            .await
+            .unwrap(),
+        );
+        assert!(paths.contains(&"/posts/archived-theme-notes/".to_string()));
+        assert_eq!(paths.len(), 5);
+
+        // WordPress uses a sitemap index
+        let paths = to_paths(
+            sitemap_urls(
+                &clients,
+                &Url::parse("https://wordpress.example.com/wp-sitemap.xml").unwrap(),
+            )
+            .await
+            .unwrap(),
+        );
+        assert_eq!(
+            paths,
+            [
+                "/2023/06/10/gardening-composting-small-spaces/",
+                "/2023/05/28/seed-starting-indoors/",
+                "/about/"
+            ]
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use gh_gql_schema::Discussion;
+use serde::Serialize;
+use url::Url;
+
+use crate::{
+    apply_labels, check_embed, find_or_create_discussion, run_hooks, seed_discussion, Error,
+    HttpClients, Post,
+};
+
+/// What was done for a post during a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// A new discussion was created.
+    Created,
+
+    /// A discussion already existed for the post.
+    Exists,
+
+    /// The existing discussion of the post was found under its previous URL, and retitled (see
+    /// [`MovedPosts`](crate::MovedPosts)).
+    Renamed,
+
+    /// The body of the existing discussion was updated, as the post changed (see
+    /// [`update_post`](crate::update_post)).
+    Updated,
+
+    /// The discussion was locked, as its comment window has closed (see
+    /// [`lock_stale`](crate::lock_stale)).
+    Locked,
+
+    /// The post was removed from the site, and its discussion was left as is (see
+    /// [`reconcile`](crate::reconcile)).
+    Orphaned,
+
+    /// The post was removed from the site, and its discussion was closed.
+    Closed,
+
+    /// The post was removed from the site, and its discussion was deleted.
+    Deleted,
+
+    /// The post was intentionally not processed.
+    Skipped,
+
+    /// Processing the post failed, see [`PostReport::errors`].
+    Failed,
+}
+
+/// The outcome for a single post.
+#[derive(Debug, Clone, Serialize)]
+pub struct PostReport {
+    /// ID of the feed entry for the post, if known.
+    pub id: Option<String>,
+
+    /// Link to the blog post.
+    pub url: String,
+
+    /// The term Giscus uses to find the discussion, which is the discussion title.
+    pub mapping_term: String,
+
+    /// What was done for the post.
+    pub action: Action,
+
+    /// Link to the discussion of the post. `None` if no discussion was found or created, such as
+    /// when processing [failed](Action::Failed) before the lookup, or the post was
+    /// [skipped](Action::Skipped).
+    pub discussion_url: Option<String>,
+
+    /// Number of the discussion in the repository, `None` in the same cases as
+    /// [`discussion_url`](PostReport::discussion_url).
+    pub discussion_number: Option<i32>,
+
+    /// Name of the category the discussion is in.
+    pub discussion_category: Option<String>,
+
+    /// Errors that stopped the post from being processed. Only set when the action is
+    /// [`Failed`](Action::Failed).
+    pub errors: Vec<String>,
+
+    /// Problems that didn't stop the post from being processed, such as a missing description.
+    pub warnings: Vec<String>,
+
+    /// Time spent processing the post, in milliseconds.
+    pub duration_ms: u64,
+
+    /// GraphQL points consumed while processing the post. Mutations aren't included, as GitHub
+    /// doesn't report their cost.
+    pub graphql_cost: u64,
+}
+
+impl PostReport {
+    /// Creates a report for the post with the given action, and no discussion.
+    pub fn new(post: &Post, action: Action) -> Self {
+        Self {
+            id: post.id.clone(),
+            url: post.url.to_string(),
+            mapping_term: post.url.path().to_string(),
+            action,
+            discussion_url: None,
+            discussion_number: None,
+            discussion_category: None,
+            errors: Vec::new(),
+            warnings: match post.description {
+                Some(_) => Vec::new(),
+                None => vec!["No description was found in the post".to_string()],
+            },
+            duration_ms: 0,
+            graphql_cost: 0,
+        }
+    }
+
+    /// Creates a report for an existing discussion, whose post is at `url`.
+    pub fn for_discussion(url: &Url, discussion: &Discussion, action: Action) -> Self {
+        Self {
+            id: None,
+            url: url.to_string(),
+            mapping_term: discussion.title.clone(),
+            action,
+            discussion_url: None,
+            discussion_number: None,
+            discussion_category: None,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            duration_ms: 0,
+            graphql_cost: 0,
+        }
+        .with_discussion(discussion)
+    }
+
+    /// Records the discussion that was found or created for the post.
+    pub fn with_discussion(mut self, discussion: &Discussion) -> Self {
+        self.discussion_url = Some(discussion.url.0.clone());
+        self.discussion_number = Some(discussion.number);
+        self.discussion_category = Some(discussion.category.name.clone());
+        self
+    }
+}
+
+/// A machine-readable summary of a run, for downstream automation.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    /// When the run started.
+    pub started_at: DateTime<Utc>,
+
+    /// Whether the run only reported what it would change.
+    pub dry_run: bool,
+
+    /// Length of the run, in milliseconds. Set by [`finish`](RunReport::finish).
+    pub duration_ms: u64,
+
+    /// GraphQL points consumed during the run.
+    pub graphql_cost: u64,
+
+    /// GraphQL points remaining in the current rate limit window, if known.
+    pub graphql_remaining: Option<u64>,
+
+    /// The outcome for each post processed, in order.
+    pub posts: Vec<PostReport>,
+
+    /// Errors that stopped the run before or between posts.
+    pub errors: Vec<String>,
+}
+
+impl Default for RunReport {
+    fn default() -> Self {
+        Self {
+            started_at: Utc::now(),
+            dry_run: false,
+            duration_ms: 0,
+            graphql_cost: 0,
+            graphql_remaining: None,
+            posts: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl RunReport {
+    /// Records the duration and GraphQL usage of the run.
+    pub fn finish(&mut self, clients: &HttpClients) {
+        self.duration_ms = (Utc::now() - self.started_at).num_milliseconds().max(0) as u64;
+        self.graphql_cost = clients.rate_limit_budget.consumed();
+        self.graphql_remaining = clients.rate_limit_budget.remaining();
+    }
+
+    /// Whether any post failed, or the run stopped early.
+    pub fn failed(&self) -> bool {
+        !self.errors.is_empty() || self.posts.iter().any(|p| p.action == Action::Failed)
+    }
+
+    /// Writes the report as pretty-printed JSON.
+    pub fn write_json(&self, writer: impl io::Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+/// [Checks the embed](check_embed) of the post's page, finds or creates its discussion, and
+/// [labels](apply_labels),
+/// [seeds](seed_discussion) and runs the [hooks](run_hooks) for a new discussion, recording the
+/// outcome instead of returning an error.
+pub async fn process_post(clients: Arc<HttpClients>, post: Arc<Post>) -> PostReport {
+    let started = Instant::now();
+    let consumed = clients.rate_limit_budget.consumed();
+
+    let found = async {
+        let embed_warnings = check_embed(Arc::clone(&clients), &post).await?;
+        let (action, discussion) =
+            find_or_create_discussion(Arc::clone(&clients), Arc::clone(&post)).await?;
+        Ok::<_, Error>((action, discussion, embed_warnings))
+    }
+    .await;
+
+    let mut report = match found {
+        Ok((action, discussion, embed_warnings)) => {
+            let mut report = PostReport::new(&post, action).with_discussion(&discussion);
+            report.warnings.extend(embed_warnings);
+            let category = clients
+                .category_routes
+                .category_for(&post, &clients.discussion_category);
+            if discussion.category.name != category {
+                report.warnings.push(format!(
+                    "The discussion is in category {} instead of {category}",
+                    discussion.category.name
+                ));
+            }
+            if action == Action::Created {
+                match apply_labels(Arc::clone(&clients), &post, &discussion).await {
+                    Ok(skipped) => report.warnings.extend(
+                        skipped
+                            .into_iter()
+                            .map(|label| format!("Label {label} doesn't exist in the repository")),
+                    ),
+                    Err(e) => report
+                        .warnings
+                        .push(format!("Unable to apply labels to the discussion: {e}")),
+                }
+                if let Err(e) = seed_discussion(Arc::clone(&clients), &post, &discussion).await {
+                    report
+                        .warnings
+                        .push(format!("Unable to post the seed comment: {e}"));
+                }
+                report
+                    .warnings
+                    .extend(run_hooks(Arc::clone(&clients), &post, &discussion).await);
+            }
+            report
+        }
+        Err(e) => {
+            let mut report = PostReport::new(&post, Action::Failed);
+            report.errors.push(e.to_string());
+            report
+        }
+    };
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    report.graphql_cost = clients.rate_limit_budget.consumed() - consumed;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use gh_gql_schema::{DateTime, Discussion, DiscussionCategory, Uri};
+    use url::Url;
+
+    use crate::{Action, Post, PostReport, RunReport};
+
+    fn post() -> Post {
+        Post {
+            id: Some("https://example.com/posts/hello".to_string()),
+            description: None,
+            url: Url::parse("https://example.com/posts/hello/").unwrap(),
+            tags: Vec::new(),
+            meta: HashMap::new(),
+            giscus: None,
+        }
+    }
+
+    #[test]
+    fn test_post_report_json() {
+        let discussion = Discussion {
+            id: cynic::Id::new("D_kwDOJSVgjc4ATest"),
+            number: 42,
+            title: "/posts/hello/".to_string(),
+            created_at: DateTime("2023-06-10T12:00:00Z".to_string()),
+            url: Uri("https://github.com/example/comments/discussions/42".to_string()),
+            body: String::new(),
+            body_text: String::new(),
+            locked: false,
+            closed: false,
+            category: DiscussionCategory {
+                id: cynic::Id::new("DIC_kwDOJSVgjc4CVgpt"),
+                name: "Blogs".to_string(),
+                is_answerable: false,
+            },
+        };
+        let report = PostReport::new(&post(), Action::Created).with_discussion(&discussion);
+
+        let json = serde_json::to_value(report).unwrap();
+        assert_eq!(json["action"], "created");
+        assert_eq!(json["mapping_term"], "/posts/hello/");
+        assert_eq!(json["discussion_number"], 42);
+        assert_eq!(json["discussion_category"], "Blogs");
+    }
+
+    #[test]
+    fn test_run_report_failed() {
+        let mut report = RunReport::default();
+        report.posts.push(PostReport::new(&post(), Action::Exists));
+        assert!(!report.failed());
+
+        report.posts.push(PostReport::new(&post(), Action::Failed));
+        assert!(report.failed());
+
+        let mut output = Vec::new();
+        report.write_json(&mut output).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(json["posts"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,108 @@
+use cynic::http::CynicReqwestError;
+use reqwest::header::ACCEPT;
+use serde::Deserialize;
+
+use crate::{Error, HttpClients};
+
+/// The oldest GitHub Enterprise Server release with the GraphQL discussions API.
+pub const MIN_GHES_VERSION: (u32, u32) = (3, 6);
+
+/// Details about the GitHub server, from the [meta API](https://docs.github.com/en/rest/meta/meta).
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerMeta {
+    /// Release of GitHub Enterprise Server, such as `3.9.0`. This is not set for github.com.
+    pub installed_version: Option<String>,
+}
+
+/// Derives the REST and GraphQL API URLs from the URL of a GitHub server, such as
+/// `https://github.com` or `https://ghes.example.com`.
+pub fn api_urls(server_url: &str) -> (String, String) {
+    let server_url = server_url.trim_end_matches('/');
+    match server_url {
+        "https://github.com" => (
+            "https://api.github.com".to_string(),
+            "https://api.github.com/graphql".to_string(),
+        ),
+        _ => (
+            format!("{server_url}/api/v3"),
+            format!("{server_url}/api/graphql"),
+        ),
+    }
+}
+
+/// Confirms that the [REST API](HttpClients::github_rest_url) is reachable with the configured
+/// token, and that GitHub Enterprise Server instances are recent enough to support discussions.
+///
+/// This should be called at startup, so that a misconfigured server fails before any other work
+/// is done.
+pub async fn check_server(clients: &HttpClients) -> Result<ServerMeta, Error> {
+    let meta: ServerMeta = github_rest_json(
+        clients
+            .html
+            .get(format!("{}/meta", clients.github_rest_url))
+            .bearer_auth(clients.token_provider.token(clients).await?),
+    )
+    .await?;
+
+    if let Some(version) = &meta.installed_version {
+        match parse_version(version) {
+            Some(v) if v >= MIN_GHES_VERSION => {}
+            _ => return Err(Error::UnsupportedServer(version.clone())),
+        }
+    }
+    Ok(meta)
+}
+
+/// Parses the major and minor numbers of a GitHub Enterprise Server release.
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split('.');
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+/// Sends a request to the GitHub REST API, and deserializes the JSON response.
+pub(crate) async fn github_rest_json<T: for<'de> Deserialize<'de>>(
+    request: reqwest::RequestBuilder,
+) -> Result<T, Error> {
+    let resp = request
+        .header(ACCEPT, "application/vnd.github+json")
+        .header("X-GitHub-Api-Version", "2022-11-28")
+        .send()
+        .await?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(CynicReqwestError::ErrorResponse(status, resp.text().await?).into());
+    }
+    Ok(resp.json().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rest::{api_urls, parse_version};
+
+    #[test]
+    fn test_api_urls() {
+        assert_eq!(
+            api_urls("https://github.com"),
+            (
+                "https://api.github.com".to_string(),
+                "https://api.github.com/graphql".to_string()
+            )
+        );
+        assert_eq!(
+            api_urls("https://ghes.example.com/"),
+            (
+                "https://ghes.example.com/api/v3".to_string(),
+                "https://ghes.example.com/api/graphql".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("3.9.0"), Some((3, 9)));
+        assert_eq!(parse_version("3.10.2"), Some((3, 10)));
+        assert!(parse_version("3.5.1").unwrap() < (3, 6));
+        assert_eq!(parse_version("unknown"), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::StatusCode;
+
+/// How failed requests to GitHub are retried.
+///
+/// Rate limited requests are always retried after the period requested by GitHub (see
+/// [`max_rate_limit_wait`](crate::HttpClients::max_rate_limit_wait)), but still count
+/// towards [`max_attempts`](RetryPolicy::max_attempts).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts for each request, including the first.
+    pub max_attempts: u32,
+
+    /// Delay before the first retry. This doubles with each attempt.
+    pub base_delay: Duration,
+
+    /// Upper limit for the delay between attempts.
+    pub max_delay: Duration,
+
+    /// Randomize each delay between half and all of its value, so that concurrent runs don't
+    /// retry in lockstep.
+    pub jitter: bool,
+
+    /// HTTP status codes that are considered transient. Timeouts and connection errors are
+    /// always retried.
+    pub retryable_statuses: Vec<StatusCode>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(120),
+            jitter: true,
+            retryable_statuses: vec![
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Reads the policy from the `RETRY_*` environment variables described in
+    /// [`HttpClients::init`](crate::HttpClients::init), using the defaults for any that are unset.
+    pub(crate) fn from_env() -> Self {
+        let default = Self::default();
+        let secs = |name: &str, default: Duration| {
+            env::var(name).map_or(default, |e| {
+                Duration::from_secs(u64::from_str(e.as_str()).unwrap())
+            })
+        };
+
+        Self {
+            max_attempts: env::var("RETRY_MAX_ATTEMPTS")
+                .map_or(default.max_attempts, |e| u32::from_str(e.as_str()).unwrap())
+                .max(1),
+            base_delay: secs("RETRY_BASE_DELAY", default.base_delay),
+            max_delay: secs("RETRY_MAX_DELAY", default.max_delay),
+            jitter: env::var("RETRY_JITTER")
+                .map_or(default.jitter, |e| bool::from_str(e.as_str()).unwrap()),
+            retryable_statuses: env::var("RETRY_STATUSES").map_or(
+                default.retryable_statuses,
+                |e| {
+                    e.split(',')
+                        .map(|code| StatusCode::from_str(code.trim()).unwrap())
+                        .collect()
+                },
+            ),
+        }
+    }
+
+    /// Whether a response with this status should be retried.
+    pub fn is_retryable(&self, status: StatusCode) -> bool {
+        self.retryable_statuses.contains(&status)
+    }
+
+    /// The delay to wait after the given attempt (starting from 1) fails.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        if self.jitter && !delay.is_zero() {
+            rand::thread_rng().gen_range(delay / 2..=delay)
+        } else {
+            delay
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::StatusCode;
+
+    use crate::RetryPolicy;
+
+    #[test]
+    fn test_exponential_delay() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..RetryPolicy::default()
+        };
+
+        assert_eq!(policy.delay(1), Duration::from_secs(5));
+        assert_eq!(policy.delay(3), Duration::from_secs(20));
+        assert_eq!(policy.delay(10), Duration::from_secs(120));
+        assert!(policy.is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(!policy.is_retryable(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn test_jitter_delay() {
+        let policy = RetryPolicy::default();
+
+        for attempt in 1..=5 {
+            let delay = policy.delay(attempt);
+            let max = Duration::from_secs(5 * 2_u64.pow(attempt - 1));
+            assert!(
+                delay >= max / 2 && delay <= max,
+                "Attempt {attempt}: {delay:?}"
+            );
+        }
+    }
+}
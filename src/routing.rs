@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+use cynic::Id;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+use crate::gql::find_category_id;
+use crate::{Error, HttpClients, Post};
+
+/// Ordered rules picking the discussion category of each post, for sites whose sections map to
+/// different categories. The first matching rule is used, and posts matching no rule use the
+/// [default category](HttpClients::discussion_category).
+#[derive(Debug, Clone, Default)]
+pub struct CategoryRoutes {
+    pub routes: Vec<CategoryRoute>,
+
+    /// IDs of the categories resolved so far, shared between clones of this struct.
+    ids: Arc<Mutex<HashMap<String, Id>>>,
+}
+
+/// A rule routing the posts it matches to a category. Every condition that is set must match, so
+/// a rule without conditions matches every post.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CategoryRoute {
+    /// Name of the discussion category.
+    pub category: String,
+
+    /// Start of the post's URL path, such as `/notes/`.
+    pub path_prefix: Option<String>,
+
+    /// Regular expression matched against the post's URL path.
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub path_regex: Option<Regex>,
+
+    /// One of the post's [tags](Post::tags), which include its feed entry categories. Matched
+    /// case-insensitively.
+    pub tag: Option<String>,
+
+    /// Values of the page's `<meta>` tags, keyed by `name` or `property`, such as
+    /// `{ "article:section": "Talks" }`.
+    #[serde(default)]
+    pub meta: HashMap<String, String>,
+}
+
+fn deserialize_regex<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Regex>, D::Error> {
+    Option::<String>::deserialize(d)?
+        .map(|pattern| Regex::new(&pattern).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+impl CategoryRoute {
+    /// Whether the post matches every condition of the rule.
+    // `Option::is_none_or` would need Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn matches(&self, post: &Post) -> bool {
+        let path = post.url.path();
+        self.path_prefix
+            .as_ref()
+            .map_or(true, |prefix| path.starts_with(prefix.as_str()))
+            && self
+                .path_regex
+                .as_ref()
+                .map_or(true, |regex| regex.is_match(path))
+            && self.tag.as_ref().map_or(true, |tag| {
+                post.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+            })
+            && self
+                .meta
+                .iter()
+                .all(|(name, value)| post.meta.get(name) == Some(value))
+    }
+}
+
+impl CategoryRoutes {
+    /// Reads the rules from the `CATEGORY_ROUTES` environment variable, a JSON array of
+    /// [`CategoryRoute`], for example:
+    ///
+    /// ```json
+    /// [
+    ///   { "path_prefix": "/notes/", "category": "Notes" },
+    ///   { "path_regex": "^/talks/\\d{4}/", "category": "Talks" },
+    ///   { "tag": "Announcement", "category": "Announcements" },
+    ///   { "meta": { "article:section": "Talks" }, "category": "Talks" }
+    /// ]
+    /// ```
+    pub(crate) fn from_env() -> Self {
+        let routes = env::var("CATEGORY_ROUTES")
+            .map(|json| serde_json::from_str(&json).expect("Invalid CATEGORY_ROUTES JSON"))
+            .unwrap_or_default();
+        Self::new(routes)
+    }
+
+    pub fn new(routes: Vec<CategoryRoute>) -> Self {
+        Self {
+            routes,
+            ids: Arc::default(),
+        }
+    }
+
+    /// The name of the category for the post, or `default` if no rule matches.
+    pub fn category_for<'a>(&'a self, post: &Post, default: &'a str) -> &'a str {
+        self.routes
+            .iter()
+            .find(|route| route.matches(post))
+            .map_or(default, |route| route.category.as_str())
+    }
+
+    /// The names of every category posts can be routed to, starting with `default`.
+    pub fn categories<'a>(&'a self, default: &'a str) -> Vec<&'a str> {
+        let mut categories = vec![default];
+        for route in &self.routes {
+            if !categories.contains(&route.category.as_str()) {
+                categories.push(&route.category);
+            }
+        }
+        categories
+    }
+}
+
+/// Where [`discussion_exists`](crate::gql::discussion_exists) looks for the existing discussion of
+/// a post, such as one a moderator moved to another category.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DuplicateSearch {
+    /// Only the category the post is routed to.
+    #[default]
+    Category,
+
+    /// Every category in the repository.
+    Repository,
+
+    /// The category the post is routed to, then each of these categories in order.
+    Categories(Vec<String>),
+}
+
+impl DuplicateSearch {
+    /// Reads the categories to search from the `DUPLICATE_SEARCH_CATEGORIES` environment
+    /// variable, either `*` for every category or a comma-separated list of category names.
+    pub(crate) fn from_env() -> Self {
+        match env::var("DUPLICATE_SEARCH_CATEGORIES") {
+            Ok(categories) => Self::parse(&categories),
+            Err(_) => Self::Category,
+        }
+    }
+
+    fn parse(categories: &str) -> Self {
+        if categories.trim() == "*" {
+            return Self::Repository;
+        }
+        let categories: Vec<String> = categories
+            .split(',')
+            .map(str::trim)
+            .filter(|category| !category.is_empty())
+            .map(str::to_string)
+            .collect();
+        match categories.is_empty() {
+            true => Self::Category,
+            false => Self::Categories(categories),
+        }
+    }
+}
+
+/// Retrieves the ID of the named category, resolving it once per run.
+pub(crate) async fn category_id(clients: Arc<HttpClients>, name: &str) -> Result<Arc<Id>, Error> {
+    if let Some(id) = clients.category_routes.ids.lock().unwrap().get(name) {
+        return Ok(Arc::new(id.clone()));
+    }
+
+    let id = find_category_id(Arc::clone(&clients), name).await?;
+    clients
+        .category_routes
+        .ids
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), id.clone());
+    Ok(Arc::new(id))
+}
+
+/// Retrieves the ID of the category the post is [routed](HttpClients::category_routes) to.
+pub(crate) async fn post_category_id(
+    clients: Arc<HttpClients>,
+    post: &Post,
+) -> Result<Arc<Id>, Error> {
+    let name = clients
+        .category_routes
+        .category_for(post, &clients.discussion_category)
+        .to_string();
+    category_id(clients, &name).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use url::Url;
+
+    use crate::routing::{CategoryRoute, CategoryRoutes, DuplicateSearch};
+    use crate::Post;
+
+    fn post(path: &str, tags: &[&str], meta: &[(&str, &str)]) -> Post {
+        Post {
+            id: None,
+            description: None,
+            url: Url::parse("https://example.com")
+                .unwrap()
+                .join(path)
+                .unwrap(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            meta: meta
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            giscus: None,
+        }
+    }
+
+    #[test]
+    fn test_category_for() {
+        let routes: Vec<CategoryRoute> = serde_json::from_str(
+            r#"[
+                { "path_prefix": "/notes/", "category": "Notes" },
+                { "path_regex": "^/talks/\\d{4}/", "category": "Talks" },
+                { "tag": "announcement", "category": "Announcements" },
+                { "meta": { "article:section": "Talks" }, "category": "Talks" }
+            ]"#,
+        )
+        .unwrap();
+        let routes = CategoryRoutes::new(routes);
+        let category = |post: Post| routes.category_for(&post, "Blogs").to_string();
+
+        assert_eq!(category(post("/notes/til-rust/", &[], &[])), "Notes");
+        assert_eq!(category(post("/talks/2023/rustconf/", &[], &[])), "Talks");
+        assert_eq!(category(post("/talks/index.html", &[], &[])), "Blogs");
+        assert_eq!(
+            category(post("/blog/v2-released/", &["Announcement"], &[])),
+            "Announcements"
+        );
+        assert_eq!(
+            category(post("/blog/slides/", &[], &[("article:section", "Talks")])),
+            "Talks"
+        );
+        assert_eq!(category(post("/blog/hello/", &["rust"], &[])), "Blogs");
+
+        // The first matching rule wins
+        assert_eq!(
+            category(post("/notes/launch/", &["announcement"], &[])),
+            "Notes"
+        );
+
+        assert_eq!(
+            routes.categories("Blogs"),
+            ["Blogs", "Notes", "Talks", "Announcements"]
+        );
+    }
+
+    #[test]
+    fn test_all_conditions_match() {
+        let route = CategoryRoute {
+            category: "Rust notes".to_string(),
+            path_prefix: Some("/notes/".to_string()),
+            path_regex: None,
+            tag: Some("rust".to_string()),
+            meta: HashMap::new(),
+        };
+        assert!(route.matches(&post("/notes/lifetimes/", &["Rust"], &[])));
+        assert!(!route.matches(&post("/notes/css-grid/", &["css"], &[])));
+        assert!(!route.matches(&post("/blog/lifetimes/", &["rust"], &[])));
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        assert!(serde_json::from_str::<CategoryRoute>(
+            r#"{ "path_regex": "(", "category": "Notes" }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_duplicate_search() {
+        assert_eq!(DuplicateSearch::parse("*"), DuplicateSearch::Repository);
+        assert_eq!(DuplicateSearch::parse(" , "), DuplicateSearch::Category);
+        assert_eq!(
+            DuplicateSearch::parse("General, Q&A"),
+            DuplicateSearch::Categories(vec!["General".to_string(), "Q&A".to_string()])
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Deserializer};
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Instant};
+use url::Url;
+
+/// The default `User-Agent` for the [HTML client](crate::HttpClients::html), identifying the
+/// crate and where to find out more about it.
+pub const DEFAULT_USER_AGENT: &str = concat!(
+    "rss_autogen_giscus/",
+    env!("CARGO_PKG_VERSION"),
+    " (+",
+    env!("CARGO_PKG_REPOSITORY"),
+    ")"
+);
+
+/// How the [HTML client](crate::HttpClients::html) fetches feeds and post pages.
+#[derive(Debug, Clone)]
+pub struct ScrapeConfig {
+    /// `User-Agent` sent with every request from the HTML client, including those to the GitHub
+    /// REST API.
+    pub user_agent: String,
+
+    /// Minimum time between requests to the same host, unless overridden in
+    /// [`sites`](ScrapeConfig::sites).
+    pub request_interval: Duration,
+
+    /// Options for specific sites, keyed by host name (such as `staging.example.com`).
+    pub sites: HashMap<String, SiteOptions>,
+
+    /// When each host can next be requested, shared between clones of this struct.
+    next_request: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+/// Options for fetching pages from a single host, such as a private or staging site.
+///
+/// Header values and passwords are redacted from the `Debug` output, as they often hold secrets.
+#[derive(Clone, Default, Deserialize)]
+pub struct SiteOptions {
+    /// Extra headers sent with each request to the host.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+
+    /// Credentials for HTTP basic authentication.
+    pub basic_auth: Option<BasicAuth>,
+
+    /// Overrides [`request_interval`](ScrapeConfig::request_interval) for this host, in seconds.
+    #[serde(default, deserialize_with = "deserialize_secs")]
+    pub interval: Option<Duration>,
+}
+
+/// Credentials for HTTP basic authentication.
+#[derive(Clone, Deserialize)]
+pub struct BasicAuth {
+    pub username: String,
+    pub password: Option<String>,
+}
+
+impl Debug for SiteOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SiteOptions")
+            .field(
+                "headers",
+                &self
+                    .headers
+                    .keys()
+                    .map(|name| (name, "<redacted>"))
+                    .collect::<HashMap<_, _>>(),
+            )
+            .field("basic_auth", &self.basic_auth)
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+impl Debug for BasicAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BasicAuth")
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+fn deserialize_secs<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+    Ok(Option::<f64>::deserialize(d)?.map(Duration::from_secs_f64))
+}
+
+impl Default for ScrapeConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            request_interval: Duration::from_secs(1),
+            sites: HashMap::new(),
+            next_request: Arc::default(),
+        }
+    }
+}
+
+impl ScrapeConfig {
+    /// Reads the configuration from the environment variables described in
+    /// [`HttpClients::init`](crate::HttpClients::init), using the defaults for any that are unset.
+    ///
+    /// `SITE_CONFIG` is a JSON object mapping host names to [`SiteOptions`], for example:
+    ///
+    /// ```json
+    /// {
+    ///   "staging.example.com": {
+    ///     "headers": { "X-Preview-Token": "..." },
+    ///     "basic_auth": { "username": "preview", "password": "..." },
+    ///     "interval": 5
+    ///   }
+    /// }
+    /// ```
+    pub(crate) fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            user_agent: env::var("HTML_USER_AGENT").unwrap_or(default.user_agent),
+            request_interval: env::var("REQUEST_INTERVAL").map_or(default.request_interval, |e| {
+                Duration::from_secs_f64(f64::from_str(e.as_str()).unwrap())
+            }),
+            sites: env::var("SITE_CONFIG").map_or(default.sites, |e| {
+                serde_json::from_str(&e).expect("SITE_CONFIG is not valid JSON site options")
+            }),
+            ..default
+        }
+    }
+
+    /// Creates a GET request for the URL, with any headers and credentials configured for its
+    /// host.
+    pub(crate) fn get(&self, client: &Client, url: &Url) -> RequestBuilder {
+        let mut request = client.get(url.clone());
+        if let Some(site) = url.host_str().and_then(|host| self.sites.get(host)) {
+            for (name, value) in &site.headers {
+                request = request.header(name, value);
+            }
+            if let Some(auth) = &site.basic_auth {
+                request = request.basic_auth(&auth.username, auth.password.as_ref());
+            }
+        }
+        request
+    }
+
+    /// Waits until the host of the URL can be requested again, and reserves the next slot.
+    pub(crate) async fn pace(&self, url: &Url) {
+        let host = url.host_str().unwrap_or_default();
+        let interval = self
+            .sites
+            .get(host)
+            .and_then(|site| site.interval)
+            .unwrap_or(self.request_interval);
+
+        let start = {
+            let mut next_request = self.next_request.lock().await;
+            let now = Instant::now();
+            let start = next_request.get(host).map_or(now, |&next| next.max(now));
+            next_request.insert(host.to_string(), start + interval);
+            start
+        };
+        sleep_until(start).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use reqwest::header::AUTHORIZATION;
+    use reqwest::Client;
+    use tokio::time::Instant;
+    use url::Url;
+
+    use crate::scrape::{ScrapeConfig, SiteOptions, DEFAULT_USER_AGENT};
+
+    #[test]
+    fn test_default_user_agent() {
+        assert!(DEFAULT_USER_AGENT
+            .starts_with(concat!("rss_autogen_giscus/", env!("CARGO_PKG_VERSION"))));
+        assert!(!DEFAULT_USER_AGENT.contains("Mozilla"));
+    }
+
+    #[test]
+    fn test_site_options() {
+        let sites: HashMap<String, SiteOptions> = serde_json::from_str(
+            r#"{
+                "staging.example.com": {
+                    "headers": { "X-Preview-Token": "preview_token" },
+                    "basic_auth": { "username": "preview", "password": "hunter2" },
+                    "interval": 0.5
+                }
+            }"#,
+        )
+        .unwrap();
+        let config = ScrapeConfig {
+            sites,
+            ..ScrapeConfig::default()
+        };
+
+        let request = config
+            .get(
+                &Client::new(),
+                &Url::parse("https://staging.example.com/feed.xml").unwrap(),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["X-Preview-Token"], "preview_token");
+        assert!(request.headers().contains_key(AUTHORIZATION));
+        let debug = format!("{config:?}");
+        assert!(!debug.contains("hunter2") && !debug.contains("preview_token"));
+
+        let request = config
+            .get(
+                &Client::new(),
+                &Url::parse("https://example.com/feed.xml").unwrap(),
+            )
+            .build()
+            .unwrap();
+        assert!(request.headers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pacing_per_host() {
+        let config = ScrapeConfig {
+            request_interval: Duration::from_millis(200),
+            ..ScrapeConfig::default()
+        };
+        let first = Url::parse("https://example.com/feed.xml").unwrap();
+        let second = Url::parse("https://example.com/posts/1").unwrap();
+        let other = Url::parse("https://other.example.com/").unwrap();
+
+        let start = Instant::now();
+        config.pace(&first).await;
+        config.pace(&other).await;
+        assert!(start.elapsed() < Duration::from_millis(200));
+
+        config.pace(&second).await;
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,272 @@
+use std::env;
+use std::fs;
+use std::sync::Arc;
+
+use cynic::GraphQlResponse;
+use feed_rs::model::Entry;
+use gh_gql_schema::{
+    AddComment, AddCommentVariables, Discussion, MarkCommentAsAnswer, MarkCommentAsAnswerVariables,
+};
+use tracing::{info, instrument};
+
+use crate::gql::{check_errors, github_gql_mutation, github_gql_mutation_once};
+use crate::post::feed_entries;
+use crate::{Error, HttpClients, Post};
+
+/// The placeholders replaced in the [seed comment template](SeedComment::template).
+const PLACEHOLDERS: [&str; 5] = ["{url}", "{path}", "{description}", "{tags}", "{related}"];
+
+/// The first comment posted on newly created discussions, so that Giscus doesn't show an empty
+/// thread.
+///
+/// GitHub's API can't pin discussions, so pinning has to be done by hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedComment {
+    /// Template of the comment. No comment is posted if unset. These placeholders are replaced:
+    ///
+    /// - `{url}` and `{path}`: the post's URL, and its path
+    /// - `{description}`: the post's description, or nothing
+    /// - `{tags}`: the post's tags, comma-separated
+    /// - `{related}`: a Markdown list of the other posts in the feed sharing a tag with the post
+    pub template: Option<String>,
+
+    /// Whether the comment is marked as the answer, in answerable (Q&A) categories.
+    pub mark_as_answer: bool,
+
+    /// Maximum number of posts listed by `{related}`.
+    pub related_limit: usize,
+}
+
+impl Default for SeedComment {
+    fn default() -> Self {
+        Self {
+            template: None,
+            mark_as_answer: false,
+            related_limit: 3,
+        }
+    }
+}
+
+impl SeedComment {
+    /// Reads the configuration from the `SEED_COMMENT` (or `SEED_COMMENT_FILE`, the path of the
+    /// template), `SEED_COMMENT_AS_ANSWER` and `SEED_RELATED_LIMIT` environment variables.
+    pub(crate) fn from_env() -> Self {
+        let template = env::var("SEED_COMMENT").ok().or_else(|| {
+            env::var("SEED_COMMENT_FILE")
+                .ok()
+                .map(|path| fs::read_to_string(path).expect("Unable to read SEED_COMMENT_FILE"))
+        });
+        Self {
+            template,
+            mark_as_answer: env::var("SEED_COMMENT_AS_ANSWER")
+                .map(|e| {
+                    e.parse()
+                        .expect("SEED_COMMENT_AS_ANSWER must be true or false")
+                })
+                .unwrap_or(false),
+            related_limit: env::var("SEED_RELATED_LIMIT")
+                .map(|e| e.parse().expect("SEED_RELATED_LIMIT must be a number"))
+                .unwrap_or(3),
+        }
+    }
+
+    /// Fills in the template for the post. `related` is only used for the `{related}`
+    /// placeholder.
+    ///
+    /// The template is scanned once, so placeholders in the post's own content (such as its
+    /// description) are left as is.
+    pub fn render(&self, template: &str, post: &Post, related: &[Entry]) -> String {
+        let mut rendered = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start..];
+            match PLACEHOLDERS.iter().find(|p| rest.starts_with(*p)) {
+                Some(placeholder) => {
+                    rendered.push_str(&self.placeholder(placeholder, post, related));
+                    rest = &rest[placeholder.len()..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+
+    /// The value of one of the [`PLACEHOLDERS`] for the post.
+    fn placeholder(&self, placeholder: &str, post: &Post, related: &[Entry]) -> String {
+        match placeholder {
+            "{url}" => post.url.to_string(),
+            "{path}" => post.url.path().to_string(),
+            "{description}" => post.description.clone().unwrap_or_default(),
+            "{tags}" => post.tags.join(", "),
+            "{related}" => self.related_posts(post, related),
+            _ => unreachable!("Unknown placeholder {placeholder}"),
+        }
+    }
+
+    /// A Markdown list of the entries sharing a tag with the post, in feed order.
+    fn related_posts(&self, post: &Post, entries: &[Entry]) -> String {
+        entries
+            .iter()
+            .filter(|entry| {
+                entry.categories.iter().any(|category| {
+                    post.tags
+                        .iter()
+                        .any(|tag| tag.eq_ignore_ascii_case(&category.term))
+                })
+            })
+            .filter_map(|entry| {
+                let link = &entry.links.first()?.href;
+                if link.trim_end_matches('/') == post.url.as_str().trim_end_matches('/') {
+                    return None;
+                }
+                let title = entry
+                    .title
+                    .as_ref()
+                    .map_or(link.as_str(), |t| t.content.as_str());
+                Some(format!("- [{title}]({link})"))
+            })
+            .take(self.related_limit)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Posts the [seed comment](HttpClients::seed_comment) on a newly created discussion, and marks
+/// it as the answer if configured.
+#[instrument(skip_all, fields(discussion = %discussion.url.0))]
+pub async fn seed_discussion(
+    clients: Arc<HttpClients>,
+    post: &Post,
+    discussion: &Discussion,
+) -> Result<(), Error> {
+    let seed = &clients.seed_comment;
+    let template = match &seed.template {
+        Some(template) => template,
+        None => return Ok(()),
+    };
+    let related = match template.contains("{related}") {
+        true => feed_entries(&clients).await?,
+        false => Vec::new(),
+    };
+
+    // Not retried, so that a timed out attempt doesn't post the comment twice
+    let resp: GraphQlResponse<AddComment> = github_gql_mutation_once(
+        Arc::clone(&clients),
+        AddCommentVariables {
+            discussion_id: discussion.id.clone(),
+            body: seed.render(template, post, &related),
+        },
+    )
+    .await?;
+    check_errors(&resp)?;
+    info!("Posted seed comment");
+
+    let comment = resp
+        .data
+        .and_then(|d| d.add_discussion_comment)
+        .and_then(|payload| payload.comment);
+    if !seed.mark_as_answer || !discussion.category.is_answerable {
+        return Ok(());
+    }
+    if let Some(comment) = comment {
+        let resp: GraphQlResponse<MarkCommentAsAnswer> = github_gql_mutation(
+            Arc::clone(&clients),
+            MarkCommentAsAnswerVariables {
+                comment_id: comment.id,
+            },
+        )
+        .await?;
+        check_errors(&resp)?;
+        info!("Marked seed comment as the answer");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use feed_rs::parser::parse;
+    use url::Url;
+
+    use crate::seed::SeedComment;
+    use crate::Post;
+
+    const FEED: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Example</title>
+  <id>https://example.com/</id>
+  <updated>2023-06-10T12:00:00Z</updated>
+  <entry>
+    <title>Hello</title>
+    <id>https://example.com/posts/hello/</id>
+    <link href="https://example.com/posts/hello/"/>
+    <updated>2023-06-10T12:00:00Z</updated>
+    <category term="rust"/>
+  </entry>
+  <entry>
+    <title>Lifetimes</title>
+    <id>https://example.com/posts/lifetimes/</id>
+    <link href="https://example.com/posts/lifetimes/"/>
+    <updated>2023-06-01T12:00:00Z</updated>
+    <category term="Rust"/>
+  </entry>
+  <entry>
+    <title>CSS grid</title>
+    <id>https://example.com/posts/css-grid/</id>
+    <link href="https://example.com/posts/css-grid/"/>
+    <updated>2023-05-01T12:00:00Z</updated>
+    <category term="css"/>
+  </entry>
+</feed>"#;
+
+    #[test]
+    fn test_render() {
+        let entries = parse(FEED.as_bytes()).unwrap().entries;
+        let post = Post {
+            id: None,
+            description: Some("Saying hello".to_string()),
+            url: Url::parse("https://example.com/posts/hello").unwrap(),
+            tags: vec!["rust".to_string(), "intro".to_string()],
+            meta: HashMap::new(),
+            giscus: None,
+        };
+        let seed = SeedComment::default();
+
+        assert_eq!(
+            seed.render(
+                "What did you think of {path}? ({tags})\n\nRelated:\n{related}",
+                &post,
+                &entries
+            ),
+            "What did you think of /posts/hello? (rust, intro)\n\nRelated:\n- [Lifetimes](https://example.com/posts/lifetimes/)"
+        );
+        assert_eq!(
+            seed.render("{description} {url}", &post, &[]),
+            "Saying hello https://example.com/posts/hello"
+        );
+    }
+
+    #[test]
+    fn test_render_post_placeholders() {
+        let entries = parse(FEED.as_bytes()).unwrap().entries;
+        let post = Post {
+            id: None,
+            description: Some("Use {related} and {url} in {tags}".to_string()),
+            url: Url::parse("https://example.com/posts/hello").unwrap(),
+            tags: vec!["rust".to_string(), "{path}".to_string()],
+            meta: HashMap::new(),
+            giscus: None,
+        };
+
+        assert_eq!(
+            SeedComment::default().render("{description} ({tags}) {unknown}", &post, &entries),
+            "Use {related} and {url} in {tags} (rust, {path}) {unknown}"
+        );
+    }
+}
@@ -0,0 +1,539 @@
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use glob::{MatchOptions, Pattern};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tokio::process::Command;
+use tracing::{info, warn};
+use url::Url;
+
+use crate::{Error, HttpClients, Post};
+
+/// Where the posts to create discussions for are found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostSource {
+    /// The latest entry in [the website's feed](HttpClients::website_rss_url).
+    Feed,
+
+    /// Content files added to the site repository, mapped to their published URLs.
+    Changes(ChangedPosts),
+}
+
+/// How added content files are found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeRange {
+    /// The files added by the commits of the push event that triggered the workflow, from the
+    /// event payload at `GITHUB_EVENT_PATH`. Other events fall back to the feed.
+    PushEvent(PathBuf),
+
+    /// The files added in a local git range, such as `main~3..main`.
+    Git(String),
+}
+
+/// Content files added to the site repository, and how their published URLs are built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedPosts {
+    pub range: ChangeRange,
+
+    /// Glob patterns matching the content files of posts, relative to the repository root, such
+    /// as `_posts/*.md` or `content/posts/**/*.md`.
+    pub content_paths: Vec<String>,
+
+    /// Pattern for the path of each post's URL, using the placeholders `:year`, `:month`, `:day`,
+    /// `:title`, `:section` and `:categories`. For example, Jekyll's default is
+    /// `/:categories/:year/:month/:day/:title.html`.
+    ///
+    /// The date is taken from the file name (`2023-04-03-title.md`) or the `date` front matter, and
+    /// the title from the file name, or the directory name for page bundles (`title/index.md`). A
+    /// `permalink` in the front matter takes precedence.
+    pub permalink: String,
+
+    /// Base URL of the published site.
+    pub site_url: Url,
+
+    /// Checkout of the site repository, used to read the front matter of each post.
+    pub repo_dir: PathBuf,
+}
+
+impl PostSource {
+    /// Reads the source from the environment variables described in
+    /// [`HttpClients::init`](crate::HttpClients::init).
+    pub(crate) fn from_env(website_rss_url: &str) -> Self {
+        let range = match env::var("POST_SOURCE").as_deref() {
+            Err(_) | Ok("feed") => return Self::Feed,
+            Ok("push") => ChangeRange::PushEvent(
+                env::var_os("GITHUB_EVENT_PATH")
+                    .expect("GITHUB_EVENT_PATH env var is required with POST_SOURCE=push")
+                    .into(),
+            ),
+            Ok(range) if range.contains("..") => ChangeRange::Git(range.to_string()),
+            Ok(other) => panic!(
+                "POST_SOURCE must be \"feed\", \"push\" or a git range like A..B, not {other:?}"
+            ),
+        };
+
+        let site_url = match env::var("SITE_URL") {
+            // A trailing slash is needed for URLs to be joined onto the site's path
+            Ok(url) => Url::parse(&format!("{}/", url.trim_end_matches('/')))
+                .expect("SITE_URL is not a valid URL"),
+            Err(_) => Url::parse(website_rss_url)
+                .expect("WEBSITE_RSS_URL is not a valid URL")
+                .join("/")
+                .unwrap(),
+        };
+
+        Self::Changes(ChangedPosts {
+            range,
+            content_paths: env::var("POST_PATHS").map_or_else(
+                |_| {
+                    vec![
+                        "_posts/**/*".to_string(),
+                        "content/posts/**/*.md".to_string(),
+                    ]
+                },
+                |e| e.split(',').map(|p| p.trim().to_string()).collect(),
+            ),
+            permalink: env::var("PERMALINK_PATTERN")
+                .unwrap_or_else(|_| "/:categories/:year/:month/:day/:title.html".to_string()),
+            site_url,
+            repo_dir: env::var_os("GITHUB_WORKSPACE").map_or_else(|| ".".into(), PathBuf::from),
+        })
+    }
+}
+
+/// Finds the posts to create discussions for, from the [configured source](HttpClients::post_source).
+pub async fn find_posts(clients: &HttpClients) -> Result<Vec<Arc<Post>>, Error> {
+    match changed_posts(clients, false).await? {
+        Some(posts) => Ok(posts),
+        None => Ok(vec![Post::get_latest(clients).await?]),
+    }
+}
+
+/// Finds the posts whose content files were added, or also modified if `include_modified` is set.
+///
+/// Returns `None` if posts are found from the feed, either as the [configured
+/// source](HttpClients::post_source) or because the workflow wasn't triggered by a push.
+pub(crate) async fn changed_posts(
+    clients: &HttpClients,
+    include_modified: bool,
+) -> Result<Option<Vec<Arc<Post>>>, Error> {
+    let changes = match &clients.post_source {
+        PostSource::Feed => return Ok(None),
+        PostSource::Changes(changes) => changes,
+    };
+
+    let changed_files = match &changes.range {
+        ChangeRange::PushEvent(path) => match push_event_files(path, include_modified)? {
+            Some(files) => files,
+            None => {
+                info!("The workflow wasn't triggered by a push, using the feed");
+                return Ok(None);
+            }
+        },
+        ChangeRange::Git(range) => {
+            git_changed_files(&changes.repo_dir, range, include_modified).await?
+        }
+    };
+
+    let mut posts = Vec::new();
+    for file in changes.matching_files(&changed_files) {
+        let front_matter = fs::read_to_string(changes.repo_dir.join(file))
+            .map(|contents| front_matter(&contents))
+            .unwrap_or_default();
+        let url = changes.post_url(file, &front_matter);
+        info!(%file, %url, "Found changed post");
+
+        // The feed entry ID isn't known, as the post may not be published in the feed yet
+        match Post::from_url(clients, url.clone(), None).await {
+            Ok(post) => posts.push(Arc::new(post)),
+            Err(e) if e.status() == Some(StatusCode::NOT_FOUND) => warn!(
+                %url,
+                "The post's page isn't published yet, skipping it. Run after the site is deployed"
+            ),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(Some(posts))
+}
+
+impl ChangedPosts {
+    /// Filters the changed files to the posts' content files.
+    fn matching_files<'a>(&self, files: &'a BTreeSet<String>) -> Vec<&'a String> {
+        let patterns: Vec<Pattern> = self
+            .content_paths
+            .iter()
+            .map(|p| Pattern::new(p).unwrap_or_else(|e| panic!("Invalid post path {p}: {e}")))
+            .collect();
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::default()
+        };
+
+        files
+            .iter()
+            .filter(|file| patterns.iter().any(|p| p.matches_with(file, options)))
+            .collect()
+    }
+
+    /// Builds the published URL of a content file from the permalink pattern.
+    fn post_url(&self, file: &str, front_matter: &HashMap<String, String>) -> Url {
+        let path = Path::new(file);
+        let mut stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut dir = path.parent().unwrap_or(Path::new(""));
+        // Page bundles are named after their directory
+        if matches!(stem.as_ref(), "index" | "_index") {
+            stem = dir.file_name().unwrap_or_default().to_string_lossy();
+            dir = dir.parent().unwrap_or(Path::new(""));
+        }
+
+        let (date, title) = split_date(&stem);
+        let date = date.or_else(|| front_matter.get("date").and_then(|d| split_date(d).0));
+        let (year, month, day) = date.unwrap_or_default();
+        let categories = front_matter
+            .get("categories")
+            .map(|c| c.split_whitespace().collect::<Vec<_>>().join("/"))
+            .unwrap_or_default();
+
+        let permalink = front_matter.get("permalink").unwrap_or(&self.permalink);
+        let mut url_path = permalink
+            .replace(":categories", &categories)
+            .replace(":year", year)
+            .replace(":month", month)
+            .replace(":day", day)
+            .replace(":title", title)
+            .replace(
+                ":section",
+                &dir.file_name().unwrap_or_default().to_string_lossy(),
+            );
+        while url_path.contains("//") {
+            url_path = url_path.replace("//", "/");
+        }
+
+        self.site_url
+            .join(url_path.trim_start_matches('/'))
+            .expect("Invalid permalink pattern")
+    }
+}
+
+/// Splits a `YYYY-MM-DD` prefix from a file name or date.
+fn split_date(s: &str) -> (Option<(&str, &str, &str)>, &str) {
+    fn digits(part: Option<&str>) -> Option<&str> {
+        part.filter(|p| p.chars().all(|c| c.is_ascii_digit()))
+    }
+    match (
+        digits(s.get(0..4)),
+        digits(s.get(5..7)),
+        digits(s.get(8..10)),
+    ) {
+        (Some(year), Some(month), Some(day)) if &s[4..5] == "-" && &s[7..8] == "-" => {
+            (Some((year, month, day)), s.get(11..).unwrap_or_default())
+        }
+        _ => (None, s),
+    }
+}
+
+/// Parses the scalar and list values of a post's YAML front matter. Lists are joined with spaces,
+/// as in Jekyll's `categories: jekyll update`.
+fn front_matter(contents: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let mut lines = contents.lines();
+    if lines.next().map(str::trim) != Some("---") {
+        return values;
+    }
+
+    let mut last_key: Option<String> = None;
+    for line in lines.take_while(|line| line.trim() != "---") {
+        if let (Some(key), Some(item)) = (&last_key, line.trim().strip_prefix("- ")) {
+            let value: &mut String = values.entry(key.clone()).or_default();
+            if !value.is_empty() {
+                value.push(' ');
+            }
+            value.push_str(unquote(item));
+        } else if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            let value = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                Some(list) => list.split(',').map(unquote).collect::<Vec<_>>().join(" "),
+                None => unquote(value).to_string(),
+            };
+            last_key = Some(key.trim().to_string());
+            values.insert(key.trim().to_string(), value);
+        }
+    }
+    values
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_matches(|c| c == '"' || c == '\'')
+}
+
+#[derive(Deserialize)]
+struct PushEvent {
+    commits: Option<Vec<PushCommit>>,
+}
+
+#[derive(Deserialize)]
+struct PushCommit {
+    #[serde(default)]
+    added: Vec<String>,
+    #[serde(default)]
+    modified: Vec<String>,
+    #[serde(default)]
+    removed: Vec<String>,
+}
+
+/// Reads the files added by a push event, and modified if `include_modified` is set, or `None` if
+/// the event isn't a push.
+fn push_event_files(
+    path: &Path,
+    include_modified: bool,
+) -> Result<Option<BTreeSet<String>>, Error> {
+    let event = fs::read(path)
+        .map_err(|e| format!("Unable to read event from {}: {e}", path.display()))
+        .and_then(|event| {
+            serde_json::from_slice::<PushEvent>(&event)
+                .map_err(|e| format!("Unable to parse GitHub event payload: {e}"))
+        })
+        .map_err(Error::ChangedFiles)?;
+
+    let commits = match event.commits {
+        Some(commits) => commits,
+        None => return Ok(None),
+    };
+    let mut files = BTreeSet::new();
+    for commit in commits {
+        files.extend(commit.added);
+        if include_modified {
+            files.extend(commit.modified);
+        }
+        for removed in commit.removed {
+            files.remove(&removed);
+        }
+    }
+    Ok(Some(files))
+}
+
+/// Lists the files added in a git range, and modified if `include_modified` is set.
+async fn git_changed_files(
+    repo_dir: &Path,
+    range: &str,
+    include_modified: bool,
+) -> Result<BTreeSet<String>, Error> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_dir)
+        .args([
+            "diff",
+            "--name-only",
+            "--no-renames",
+            match include_modified {
+                true => "--diff-filter=AM",
+                false => "--diff-filter=A",
+            },
+            "-z",
+            range,
+        ])
+        .output()
+        .await
+        .map_err(|e| Error::ChangedFiles(format!("Unable to run git: {e}")))?;
+    if !output.status.success() {
+        // actions/checkout only fetches the latest commit by default
+        return Err(Error::ChangedFiles(format!(
+            "Unable to list files changed in {range}, the checkout may be missing its commits. Set `fetch-depth: 0` in actions/checkout to fetch the full history. {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .filter(|f| !f.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap};
+    use std::env;
+    use std::fs;
+    use std::process::{self, Command};
+
+    use url::Url;
+
+    use crate::source::{
+        front_matter, git_changed_files, push_event_files, ChangeRange, ChangedPosts,
+    };
+
+    const JEKYLL_POST: &str = "---
+layout: post
+title:  \"Welcome to Jekyll!\"
+date:   2023-04-03 18:23:45 -0400
+categories: jekyll update
+---
+You'll find this post in your `_posts` directory.
+";
+
+    fn changes(permalink: &str) -> ChangedPosts {
+        ChangedPosts {
+            range: ChangeRange::Git("HEAD~1..HEAD".to_string()),
+            content_paths: vec![
+                "_posts/*.md".to_string(),
+                "content/posts/**/*.md".to_string(),
+            ],
+            permalink: permalink.to_string(),
+            site_url: Url::parse("https://team-role-org-testing.github.io/").unwrap(),
+            repo_dir: ".".into(),
+        }
+    }
+
+    #[test]
+    fn test_jekyll_permalink() {
+        let url = changes("/:categories/:year/:month/:day/:title.html").post_url(
+            "_posts/2023-04-03-welcome-to-jekyll.md",
+            &front_matter(JEKYLL_POST),
+        );
+        assert_eq!(
+            url.as_str(),
+            "https://team-role-org-testing.github.io/jekyll/update/2023/04/03/welcome-to-jekyll.html"
+        );
+    }
+
+    #[test]
+    fn test_hugo_permalink() {
+        let changes = changes("/:section/:title/");
+        let front_matter = HashMap::from([("date".to_string(), "2023-06-10".to_string())]);
+
+        assert_eq!(
+            changes
+                .post_url("content/posts/hugo-modules.md", &HashMap::new())
+                .path(),
+            "/posts/hugo-modules/"
+        );
+        assert_eq!(
+            changes
+                .post_url("content/posts/page-bundle/index.md", &front_matter)
+                .path(),
+            "/posts/page-bundle/"
+        );
+
+        let front_matter = HashMap::from([("permalink".to_string(), "/about/".to_string())]);
+        assert_eq!(
+            changes
+                .post_url("content/posts/about.md", &front_matter)
+                .path(),
+            "/about/"
+        );
+    }
+
+    #[test]
+    fn test_front_matter_lists() {
+        let values = front_matter("---\ncategories: [\"rust\", web]\ntags:\n  - a\n  - b\n---\n");
+        assert_eq!(values["categories"], "rust web");
+        assert_eq!(values["tags"], "a b");
+        assert!(front_matter("No front matter").is_empty());
+    }
+
+    #[test]
+    fn test_matching_files() {
+        let files = BTreeSet::from([
+            "_posts/2023-04-03-welcome-to-jekyll.md".to_string(),
+            "_posts/drafts/unfinished.md".to_string(),
+            "content/posts/2023/bundle/index.md".to_string(),
+            "README.md".to_string(),
+        ]);
+
+        assert_eq!(
+            changes("").matching_files(&files),
+            [
+                "_posts/2023-04-03-welcome-to-jekyll.md",
+                "content/posts/2023/bundle/index.md"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_push_event() {
+        let path = env::temp_dir().join(format!(
+            "rss_autogen_giscus_test_event_{}.json",
+            process::id()
+        ));
+        fs::write(
+            &path,
+            r#"{
+                "commits": [
+                    { "added": ["_posts/2023-06-01-first.md", "_posts/2023-06-02-typo.md"], "removed": [] },
+                    { "added": ["_posts/2023-06-03-second.md"], "modified": ["_posts/2023-05-01-old.md"], "removed": ["_posts/2023-06-02-typo.md"] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            push_event_files(&path, false).unwrap().unwrap(),
+            BTreeSet::from([
+                "_posts/2023-06-01-first.md".to_string(),
+                "_posts/2023-06-03-second.md".to_string()
+            ])
+        );
+        assert_eq!(
+            push_event_files(&path, true).unwrap().unwrap(),
+            BTreeSet::from([
+                "_posts/2023-05-01-old.md".to_string(),
+                "_posts/2023-06-01-first.md".to_string(),
+                "_posts/2023-06-03-second.md".to_string()
+            ])
+        );
+
+        fs::write(&path, r#"{ "inputs": {} }"#).unwrap();
+        assert!(push_event_files(&path, false).unwrap().is_none());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_git_range() {
+        let repo = env::temp_dir().join(format!("rss_autogen_giscus_test_repo_{}", process::id()));
+        let _ = fs::remove_dir_all(&repo);
+        fs::create_dir_all(repo.join("_posts")).unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+
+        git(&["init", "-q"]);
+        fs::write(repo.join("README.md"), "Site").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-qm", "Initial commit"]);
+        fs::write(repo.join("_posts/2023-06-01-first.md"), JEKYLL_POST).unwrap();
+        fs::write(repo.join("README.md"), "Updated site").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-qm", "Add post"]);
+
+        assert_eq!(
+            git_changed_files(&repo, "HEAD~1..HEAD", false)
+                .await
+                .unwrap(),
+            BTreeSet::from(["_posts/2023-06-01-first.md".to_string()])
+        );
+        assert_eq!(
+            git_changed_files(&repo, "HEAD~1..HEAD", true)
+                .await
+                .unwrap(),
+            BTreeSet::from([
+                "README.md".to_string(),
+                "_posts/2023-06-01-first.md".to_string()
+            ])
+        );
+        // Commits outside a shallow checkout
+        let missing = git_changed_files(&repo, "HEAD~5..HEAD", false).await;
+        assert!(missing.unwrap_err().to_string().contains("fetch-depth: 0"));
+        fs::remove_dir_all(repo).unwrap();
+    }
+}
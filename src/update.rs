@@ -0,0 +1,285 @@
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::Instant;
+
+use cynic::GraphQlResponse;
+use gh_gql_schema::{Discussion, UpdateDiscussionBody, UpdateDiscussionBodyVariables};
+use tracing::{info, instrument};
+use url::Url;
+
+use crate::embed::{is_mapping_title, GISCUS_HASH};
+use crate::gql::{
+    category_discussions, check_errors, discussion_body, discussion_exists, github_gql_mutation,
+    search_categories,
+};
+use crate::moved::ID_MARKER;
+use crate::post::feed_entries;
+use crate::reconcile::sitemap_urls;
+use crate::routing::{category_id, post_category_id};
+use crate::source::changed_posts;
+use crate::{Action, Error, HttpClients, Post, PostReport};
+
+/// Marks the start of a hand-edited section of a discussion body, which is kept when the body is
+/// updated.
+pub const KEEP_START: &str = "<!-- rss_autogen_giscus:keep -->";
+
+/// Marks the end of a hand-edited section, started by [`KEEP_START`].
+pub const KEEP_END: &str = "<!-- rss_autogen_giscus:end -->";
+
+/// Finds the posts whose discussion may need updating.
+///
+/// With a push event or git range as the [source](HttpClients::post_source), these are the posts
+/// whose content files were added or modified. Otherwise, they are the posts in the feed, and in
+/// the sitemap at `sitemap_url` if given, that have a discussion.
+#[instrument(skip_all)]
+pub async fn find_posts_to_update(
+    clients: Arc<HttpClients>,
+    sitemap_url: Option<&Url>,
+) -> Result<Vec<Arc<Post>>, Error> {
+    if let Some(posts) = changed_posts(&clients, true).await? {
+        return Ok(posts);
+    }
+
+    let titles = discussion_titles(Arc::clone(&clients)).await?;
+    let has_discussion = |url: &Url| {
+        titles
+            .iter()
+            .any(|title| is_mapping_title(title, url.path()))
+    };
+
+    let mut seen = HashSet::new();
+    let mut posts = Vec::new();
+    for entry in feed_entries(&clients).await? {
+        let url: Url = match entry.links.first().and_then(|link| link.href.parse().ok()) {
+            Some(url) => url,
+            None => continue,
+        };
+        if has_discussion(&url) && seen.insert(url.path().to_string()) {
+            posts.push(Arc::new(Post::from_entry(&clients, url, entry).await?));
+        }
+    }
+    if let Some(sitemap_url) = sitemap_url {
+        for url in sitemap_urls(&clients, sitemap_url).await? {
+            if has_discussion(&url) && seen.insert(url.path().to_string()) {
+                posts.push(Arc::new(Post::from_url(&clients, url, None).await?));
+            }
+        }
+    }
+    Ok(posts)
+}
+
+/// The titles of the discussions in the categories posts are [routed](HttpClients::category_routes)
+/// to, and the other categories of the [duplicate search](HttpClients::duplicate_search).
+async fn discussion_titles(clients: Arc<HttpClients>) -> Result<HashSet<String>, Error> {
+    let mut search_ids = Vec::new();
+    for category in clients
+        .category_routes
+        .categories(&clients.discussion_category)
+    {
+        let cat_id = category_id(Arc::clone(&clients), category).await?;
+        for search_id in search_categories(Arc::clone(&clients), cat_id).await? {
+            if !search_ids.contains(&search_id) {
+                search_ids.push(search_id);
+            }
+        }
+    }
+    // Searching every category already covers the others
+    if search_ids.contains(&None) {
+        search_ids = vec![None];
+    }
+
+    let mut titles = HashSet::new();
+    for search_id in search_ids {
+        category_discussions(Arc::clone(&clients), search_id, |discussion| {
+            titles.insert(discussion.title.clone());
+            ControlFlow::Continue(())
+        })
+        .await?;
+    }
+    Ok(titles)
+}
+
+/// Re-renders the body of the post's existing discussion, and updates the discussion if the post's
+/// description or URL changed. Hand-edited sections between [`KEEP_START`] and [`KEEP_END`], and
+/// the Giscus hash, are kept.
+///
+/// The outcome is [`Action::Updated`] or [`Action::Exists`] if the body was unchanged. Posts
+/// without a discussion are [skipped](Action::Skipped), rather than creating one. In a dry run,
+/// the discussion is left as is.
+pub async fn update_post(clients: Arc<HttpClients>, post: Arc<Post>, dry_run: bool) -> PostReport {
+    let started = Instant::now();
+    let consumed = clients.rate_limit_budget.consumed();
+
+    let mut report = match update_discussion(Arc::clone(&clients), Arc::clone(&post), dry_run).await
+    {
+        Ok(Some((action, discussion))) => {
+            PostReport::new(&post, action).with_discussion(&discussion)
+        }
+        Ok(None) => {
+            let mut report = PostReport::new(&post, Action::Skipped);
+            report
+                .warnings
+                .push("No discussion exists for the post".to_string());
+            report
+        }
+        Err(e) => {
+            let mut report = PostReport::new(&post, Action::Failed);
+            report.errors.push(e.to_string());
+            report
+        }
+    };
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    report.graphql_cost = clients.rate_limit_budget.consumed() - consumed;
+    report
+}
+
+#[instrument(skip_all, fields(post = %post.url))]
+async fn update_discussion(
+    clients: Arc<HttpClients>,
+    post: Arc<Post>,
+    dry_run: bool,
+) -> Result<Option<(Action, Discussion)>, Error> {
+    let cat_id = post_category_id(Arc::clone(&clients), &post).await?;
+    let discussion =
+        match discussion_exists(Arc::clone(&clients), Arc::clone(&post), cat_id).await? {
+            Some(discussion) => discussion,
+            None => return Ok(None),
+        };
+
+    let body = merge_body(
+        &discussion.body,
+        &discussion_body(&post, clients.giscus_strict),
+    );
+    if body == normalize(&discussion.body) {
+        return Ok(Some((Action::Exists, discussion)));
+    }
+    if dry_run {
+        info!(
+            discussion_url = discussion.url.0,
+            "Would update discussion body"
+        );
+        return Ok(Some((Action::Updated, discussion)));
+    }
+
+    let resp: GraphQlResponse<UpdateDiscussionBody> = github_gql_mutation(
+        Arc::clone(&clients),
+        UpdateDiscussionBodyVariables {
+            discussion_id: discussion.id.clone(),
+            body,
+        },
+    )
+    .await?;
+    check_errors(&resp)?;
+
+    info!(discussion_url = discussion.url.0, "Updated discussion body");
+    Ok(Some((Action::Updated, discussion)))
+}
+
+/// Appends the kept sections, Giscus hash and post ID marker of the `current` body to the
+/// `rendered` body. The ID marker is only kept if `rendered` doesn't have one.
+fn merge_body(current: &str, rendered: &str) -> String {
+    let current = normalize(current);
+    let mut body = normalize(rendered);
+    // The post's ID is unknown for some sources, so the marker of the current body is kept
+    let rendered_id = body.lines().any(|line| line.trim().starts_with(ID_MARKER));
+
+    for section in kept_sections(&current) {
+        body.push_str("\n\n");
+        body.push_str(section);
+    }
+    for line in current.lines().map(str::trim).filter(|line| {
+        line.starts_with(GISCUS_HASH) || (!rendered_id && line.starts_with(ID_MARKER))
+    }) {
+        if body.contains(line) {
+            continue;
+        }
+        body.push_str("\n\n");
+        body.push_str(line);
+    }
+    body
+}
+
+/// Finds the hand-edited sections of the body, including their markers. An unterminated section
+/// runs to the end of the body.
+fn kept_sections(body: &str) -> Vec<&str> {
+    let mut sections = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find(KEEP_START) {
+        let section = &rest[start..];
+        let end = section
+            .find(KEEP_END)
+            .map_or(section.len(), |end| end + KEEP_END.len());
+        sections.push(&section[..end]);
+        rest = &section[end..];
+    }
+    sections
+}
+
+/// Normalizes line endings and trailing whitespace, as bodies edited on GitHub use CRLF.
+fn normalize(body: &str) -> String {
+    body.replace("\r\n", "\n").trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::moved::id_marker;
+    use crate::update::{kept_sections, merge_body, KEEP_END, KEEP_START};
+
+    #[test]
+    fn test_kept_sections() {
+        let body = format!("Intro\n{KEEP_START}\nOne\n{KEEP_END}\nMiddle\n{KEEP_START}\nTwo");
+        assert_eq!(
+            kept_sections(&body),
+            [
+                format!("{KEEP_START}\nOne\n{KEEP_END}"),
+                format!("{KEEP_START}\nTwo")
+            ]
+        );
+        assert!(kept_sections("No sections").is_empty());
+    }
+
+    #[test]
+    fn test_merge_body() {
+        let current = format!(
+            "Old description\r\n\r\nhttps://example.com/posts/hello/\r\n\r\n{KEEP_START}\r\nSee also the follow-up post.\r\n{KEEP_END}\r\n\r\n<!-- sha1: 4b4ee2fdb6e2b8bb1c6cbb8d1ae2e2d3a5e4e4c1 -->\r\n"
+        );
+        let rendered = "New description\n\nhttps://example.com/posts/hello/";
+
+        assert_eq!(
+            merge_body(&current, rendered),
+            format!(
+                "New description\n\nhttps://example.com/posts/hello/\n\n{KEEP_START}\nSee also the follow-up post.\n{KEEP_END}\n\n<!-- sha1: 4b4ee2fdb6e2b8bb1c6cbb8d1ae2e2d3a5e4e4c1 -->"
+            )
+        );
+
+        // An unchanged body is left as is
+        let current = "Description\r\n\r\nhttps://example.com/posts/hello/\r\n";
+        assert_eq!(
+            merge_body(current, "Description\n\nhttps://example.com/posts/hello/"),
+            current.replace("\r\n", "\n").trim_end()
+        );
+
+        // The hash isn't repeated when the rendered body already has it
+        let hash = "<!-- sha1: 2ffba58dbd69d5b6f4828c742d3873779769e688 -->";
+        let rendered = format!("https://example.com/posts/hello/\n\n{hash}");
+        assert_eq!(merge_body(&rendered, &rendered), rendered);
+    }
+
+    #[test]
+    fn test_merge_body_id_marker() {
+        let marker = id_marker("https://example.com/?p=42");
+        let current = format!("Description\n\nhttps://example.com/posts/hello/\n\n{marker}");
+
+        // Kept when the post's ID is unknown
+        assert_eq!(
+            merge_body(&current, "Description\n\nhttps://example.com/posts/hello/"),
+            current
+        );
+
+        // Replaced when the post's ID is known
+        let new_marker = id_marker("https://example.com/?p=43");
+        let rendered = format!("Description\n\nhttps://example.com/posts/hello/\n\n{new_marker}");
+        assert_eq!(merge_body(&current, &rendered), rendered);
+    }
+}